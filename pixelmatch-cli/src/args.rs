@@ -0,0 +1,116 @@
+use pixelmatch_snapshot::Error;
+use std::str::FromStr;
+
+/// command line arguments of a subcommand, split into positionals and `--name` options
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Args {
+    positionals: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// splits `args` into positionals and options; `flags` are options without a value,
+    /// `values` are options followed by a value, as `--name value` or `--name=value`.
+    /// any other option is an error, and so is everything after `--` being an option.
+    pub fn parse(args: &[String], flags: &[&str], values: &[&str]) -> Result<Self, Error> {
+        let mut parsed = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positionals.extend(args.cloned());
+                break;
+            }
+            let Some(option) = arg.strip_prefix("--") else {
+                parsed.positionals.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (option, None),
+            };
+            if flags.contains(&name) && value.is_none() {
+                parsed.options.push((name.to_string(), None));
+            } else if values.contains(&name) {
+                let value = match value {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| format!("`--{name}` needs a value"))?,
+                };
+                parsed.options.push((name.to_string(), Some(value)));
+            } else {
+                return Err(format!("unknown option `{arg}`").into());
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// arguments that are not options, in order
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    /// whether a flag was given
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    /// the value of an option; the last one wins if it was given more than once
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    /// the value of an option parsed as `T`
+    pub fn parse_value<T: FromStr>(&self, name: &str) -> Result<Option<T>, Error> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid value `{value}` for `--{name}`").into())
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn should_split_positionals_flags_and_values() {
+        let args = Args::parse(
+            &strings(&[
+                "a*",
+                "--all",
+                "--dir",
+                "shots",
+                "--threshold=0.2",
+                "--",
+                "--b",
+            ]),
+            &["all"],
+            &["dir", "threshold"],
+        )
+        .unwrap();
+        assert_eq!(args.positionals(), &strings(&["a*", "--b"])[..]);
+        assert!(args.flag("all"));
+        assert_eq!(args.value("dir"), Some("shots"));
+        assert_eq!(args.parse_value::<f32>("threshold").unwrap(), Some(0.2));
+        assert_eq!(args.parse_value::<f32>("missing").unwrap(), None);
+
+        assert!(Args::parse(&strings(&["--unknown"]), &["all"], &["dir"]).is_err());
+        assert!(Args::parse(&strings(&["--dir"]), &[], &["dir"]).is_err());
+        assert!(Args::parse(&strings(&["--all=yes"]), &["all"], &[]).is_err());
+        let args = Args::parse(&strings(&["--dir", "x"]), &[], &["dir"]).unwrap();
+        assert!(args.parse_value::<u32>("dir").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::args::Args;
+use crate::report::Report;
+use crate::review::{options, relative};
+use notify::{RecursiveMode, Watcher as _};
+use pixelmatch::PixelmatchOption;
+use pixelmatch_snapshot::watch::Watcher;
+use pixelmatch_snapshot::{Error, Outcome};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// how long to wait for more file events before comparing, so that a burst of writes
+/// is compared once
+const SETTLE: Duration = Duration::from_millis(100);
+/// how often a watched report reloads itself in the browser, in seconds
+const REPORT_REFRESH: u32 = 2;
+
+/// `pixelmatch check`; compares the screenshots under a directory with the baselines at
+/// the same relative paths, once or, with `--watch`, again whenever they change
+pub fn check<W: Write>(args: &[String], out: &mut W) -> Result<(), Error> {
+    let args = Args::parse(args, &["watch"], &["report", "threshold"])?;
+    let [screenshots, baselines] = args.positionals() else {
+        return Err("give the screenshot and the baseline directories".into());
+    };
+    let (screenshots, baselines) = (PathBuf::from(screenshots), PathBuf::from(baselines));
+    let report_path = args.value("report").map(PathBuf::from);
+    let options = options(&args)?;
+    let mut session = Session {
+        watcher: Watcher::new(&screenshots),
+        screenshots: screenshots.clone(),
+        baselines,
+        options,
+        report: Report::default(),
+    };
+
+    if !args.flag("watch") {
+        let failed = session.compare(out)?;
+        if let Some(path) = &report_path {
+            session.report.write(path, None)?;
+        }
+        return match failed {
+            0 => Ok(()),
+            failed => Err(format!("{failed} screenshots don't match").into()),
+        };
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut fs_watcher = notify::recommended_watcher(tx)?;
+    fs_watcher.watch(&screenshots, RecursiveMode::Recursive)?;
+    writeln!(out, "watching {}", screenshots.display())?;
+    session.watch(&rx, report_path.as_deref(), out)
+}
+
+struct Session {
+    watcher: Watcher,
+    screenshots: PathBuf,
+    baselines: PathBuf,
+    options: Option<PixelmatchOption>,
+    report: Report,
+}
+
+impl Session {
+    /// compares the changed screenshots and updates the report, then again after each
+    /// burst of file events; returns when the events end, i.e. the file watcher is dropped
+    fn watch<W: Write>(
+        &mut self,
+        events: &Receiver<notify::Result<notify::Event>>,
+        report_path: Option<&Path>,
+        out: &mut W,
+    ) -> Result<(), Error> {
+        loop {
+            self.compare(out)?;
+            if let Some(path) = report_path {
+                self.report.write(path, Some(REPORT_REFRESH))?;
+            }
+            out.flush()?;
+            // block until something changes, then let the writes settle
+            match events.recv() {
+                Ok(Ok(_)) => {}
+                // e.g. missed events; the next pass looks at the whole directory anyway
+                Ok(Err(err)) => writeln!(out, "watch error: {err}")?,
+                Err(_) => return Ok(()),
+            }
+            while events.recv_timeout(SETTLE).is_ok() {}
+        }
+    }
+
+    /// compares the screenshots changed since the last call, printing a line for each
+    /// and updating the report; returns how many don't match
+    fn compare<W: Write>(&mut self, out: &mut W) -> Result<usize, Error> {
+        let mut failed = 0;
+        for (path, outcome) in self
+            .watcher
+            .compare_changed(&self.baselines, self.options.clone())?
+        {
+            let name = relative(&self.screenshots, &path);
+            let status = match &outcome {
+                Ok(Outcome::Matched | Outcome::Updated) => "matched".to_string(),
+                Ok(Outcome::Missing { .. }) => "no baseline".into(),
+                Ok(Outcome::SizeMismatch { expected, .. }) => {
+                    format!("the baseline is {}x{}", expected.0, expected.1)
+                }
+                Ok(Outcome::Mismatched {
+                    diff_count,
+                    diff_ratio,
+                    ..
+                }) => format!("{diff_count} pixels differ ({:.3}%)", diff_ratio * 100.0),
+                Err(err) => format!("error: {err}"),
+            };
+            if !matches!(outcome, Ok(Outcome::Matched | Outcome::Updated)) {
+                failed += 1;
+            }
+            writeln!(out, "{name}: {status}")?;
+            let baseline = self.baselines.join(Path::new(&name));
+            self.report.update(
+                &name,
+                &path,
+                &baseline,
+                outcome.map_err(|err| err.to_string()),
+            );
+        }
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::run;
+    use pixelmatch::png_stream::encode_png;
+    use std::fs::{self, File};
+
+    fn write_png(path: &Path, image: &[u8], dimensions: (u32, u32)) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        encode_png(File::create(path).unwrap(), image, dimensions).unwrap();
+    }
+
+    #[test]
+    fn should_compare_screenshots_and_write_a_report() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-cli-check-{}", std::process::id()));
+        let (screenshots, baselines) = (dir.join("screenshots"), dir.join("snapshots"));
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        write_png(&baselines.join("pages/home.png"), &white, (2, 2));
+        write_png(&baselines.join("pages/about.png"), &white, (2, 2));
+        write_png(&screenshots.join("pages/home.png"), &white, (2, 2));
+        write_png(&screenshots.join("pages/about.png"), &changed, (2, 2));
+        write_png(&screenshots.join("new.png"), &white, (2, 2));
+
+        let report = dir.join("report.html");
+        let args: Vec<String> = [
+            "check",
+            &screenshots.to_string_lossy(),
+            &baselines.to_string_lossy(),
+            "--report",
+            &report.to_string_lossy(),
+        ]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+        let mut out = vec![];
+        let err = run(&args, &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "2 screenshots don't match");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "new.png: no baseline\n\
+             pages/about.png: 1 pixels differ (25.000%)\n\
+             pages/home.png: matched\n"
+        );
+        let html = fs::read_to_string(&report).unwrap();
+        assert!(html.contains("<h1>2 of 3 screenshots differ</h1>"));
+        assert!(html.contains("about.diff.png"));
+        assert!(!html.contains("refresh"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn session(dir: &Path) -> Session {
+        let screenshots = dir.join("screenshots");
+        Session {
+            watcher: Watcher::new(&screenshots),
+            screenshots,
+            baselines: dir.join("snapshots"),
+            options: None,
+            report: Report::default(),
+        }
+    }
+
+    #[test]
+    fn should_compare_rewritten_screenshots_and_skip_deleted_ones() {
+        let dir =
+            std::env::temp_dir().join(format!("pixelmatch-cli-check-rw-{}", std::process::id()));
+        let (screenshots, baselines) = (dir.join("screenshots"), dir.join("snapshots"));
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        for name in ["home.png", "about.png"] {
+            write_png(&baselines.join(name), &white, (2, 2));
+            write_png(&screenshots.join(name), &white, (2, 2));
+        }
+
+        let mut session = session(&dir);
+        let mut out = vec![];
+        assert_eq!(session.compare(&mut out).unwrap(), 0);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "about.png: matched
+home.png: matched
+"
+        );
+
+        write_png(&screenshots.join("home.png"), &changed, (2, 2));
+        fs::remove_file(screenshots.join("about.png")).unwrap();
+        let mut out = vec![];
+        assert_eq!(session.compare(&mut out).unwrap(), 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "home.png: 1 pixels differ (25.000%)
+"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_keep_watching_past_watch_errors() {
+        let dir =
+            std::env::temp_dir().join(format!("pixelmatch-cli-check-watch-{}", std::process::id()));
+        let white = vec![255; 2 * 2 * 4];
+        write_png(&dir.join("snapshots/home.png"), &white, (2, 2));
+        write_png(&dir.join("screenshots/home.png"), &white, (2, 2));
+
+        let (tx, rx) = mpsc::channel();
+        tx.send(Err(notify::Error::generic("events were missed")))
+            .unwrap();
+        drop(tx);
+        let report = dir.join("report.html");
+        let mut out = vec![];
+        session(&dir).watch(&rx, Some(&report), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "home.png: matched
+watch error: events were missed
+"
+        );
+        assert!(fs::read_to_string(&report).unwrap().contains("refresh"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+//! `pixelmatch git-diff`; compares PNG files for `git diff` and `git difftool`.
+//!
+//! ```text
+//! # .gitattributes
+//! *.png diff=pixelmatch
+//!
+//! # .git/config
+//! [diff "pixelmatch"]
+//!     command = pixelmatch git-diff
+//! [difftool "pixelmatch"]
+//!     cmd = pixelmatch git-diff "$LOCAL" "$REMOTE"
+//! ```
+//!
+//! as an external diff it takes git's seven arguments
+//! `path old-file old-hex old-mode new-file new-hex new-mode`, followed by `new-path rename-info`
+//! for renamed paths, or just `path` for unmerged ones; as a difftool just the two files.
+//! prints the dimensions, the diff count and the diff regions; the diff image is written to
+//! `--diff-dir`, or the directory in `PIXELMATCH_DIFF_DIR`, if set.
+
+use crate::args::Args;
+use pixelmatch::png_stream::{decode_png, encode_png};
+use pixelmatch::region::{find_regions, merge_regions};
+use pixelmatch::*;
+use pixelmatch_snapshot::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// regions closer than this are listed as one
+const REGION_MERGE_DISTANCE: u32 = 8;
+
+type Image = (Vec<u8>, (u32, u32));
+
+/// `pixelmatch git-diff`; fails if a file can't be read as a PNG image
+pub fn git_diff<W: Write>(args: &[String], out: &mut W) -> Result<(), Error> {
+    let args = Args::parse(args, &[], &["diff-dir"])?;
+    let diff_dir = args
+        .value("diff-dir")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("PIXELMATCH_DIFF_DIR").map(PathBuf::from));
+    let files = args.positionals();
+    let (path, new_path, old, new) = match files.len() {
+        // an unmerged path comes without any files
+        1 => {
+            writeln!(out, "diff --pixelmatch a/{0} b/{0}", files[0])?;
+            writeln!(out, "unmerged")?;
+            return Ok(());
+        }
+        7 => (&files[0], &files[0], &files[1], &files[4]),
+        // a renamed or copied path also gets the new name and the similarity info
+        9 => (&files[0], &files[7], &files[1], &files[4]),
+        2 => (&files[1], &files[1], &files[0], &files[1]),
+        _ => return Err("give git's external diff arguments, or the two files to compare".into()),
+    };
+    writeln!(out, "diff --pixelmatch a/{path} b/{new_path}")?;
+    diff(new_path, old, new, diff_dir.as_deref(), out)
+}
+
+fn diff<W: Write>(
+    path: &str,
+    old: &str,
+    new: &str,
+    diff_dir: Option<&Path>,
+    out: &mut W,
+) -> Result<(), Error> {
+    // git passes /dev/null for the missing side of an added or deleted file
+    let read = |file: &str| -> Result<Option<Image>, Error> {
+        if file == "/dev/null" {
+            return Ok(None);
+        }
+        read_png(Path::new(file))
+            .map(Some)
+            .map_err(|err| format!("{file}: {err}").into())
+    };
+    let (old, new) = match (read(old)?, read(new)?) {
+        (Some(old), Some(new)) => (old, new),
+        (None, Some((_, (w, h)))) => return Ok(writeln!(out, "added {w}x{h} image")?),
+        (Some((_, (w, h))), None) => return Ok(writeln!(out, "deleted {w}x{h} image")?),
+        (None, None) => return Ok(()),
+    };
+    let ((img1, dimensions), (img2, new_dimensions)) = (old, new);
+    if dimensions != new_dimensions {
+        writeln!(
+            out,
+            "dimensions: {}x{} -> {}x{}, not compared",
+            dimensions.0, dimensions.1, new_dimensions.0, new_dimensions.1
+        )?;
+        return Ok(());
+    }
+    writeln!(out, "dimensions: {}x{}", dimensions.0, dimensions.1)?;
+
+    let options = PixelmatchOption {
+        include_anti_alias: true,
+        ..PixelmatchOption::default()
+    };
+    let result = pixelmatch(&img1, &img2, dimensions, Some(options.clone()))?;
+    let pixels = (img1.len() / 4).max(1);
+    writeln!(
+        out,
+        "diff count: {} ({:.3}%)",
+        result.diff_count,
+        result.diff_count as f64 * 100.0 / pixels as f64
+    )?;
+    let regions = merge_regions(
+        &find_regions(&result.diff_image, dimensions, options.diff_color)?,
+        REGION_MERGE_DISTANCE,
+    );
+    writeln!(out, "regions: {}", regions.len())?;
+    for r in &regions {
+        writeln!(
+            out,
+            "  {}x{} at ({}, {}), {} pixels",
+            r.width, r.height, r.x, r.y, r.pixel_count
+        )?;
+    }
+
+    if let Some(dir) = diff_dir {
+        let name = format!(
+            "{}.diff.png",
+            path.trim_end_matches(".png").replace('/', "_")
+        );
+        let diff_path = dir.join(name);
+        let file = BufWriter::new(File::create(&diff_path)?);
+        encode_png(file, &result.diff_image, dimensions)?;
+        writeln!(out, "diff image: {}", diff_path.display())?;
+    }
+    Ok(())
+}
+
+fn read_png(path: &Path) -> Result<Image, Error> {
+    Ok(decode_png(BufReader::new(File::open(path)?))?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::run;
+    use pixelmatch::png_stream::encode_png;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+
+    fn setup(name: &str) -> (PathBuf, String, String) {
+        let dir = std::env::temp_dir().join(format!(
+            "pixelmatch-cli-git-diff-{name}-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        let (old, new) = (dir.join("old.png"), dir.join("new.png"));
+        encode_png(File::create(&old).unwrap(), &white, (2, 2)).unwrap();
+        encode_png(File::create(&new).unwrap(), &changed, (2, 2)).unwrap();
+        let path = |p: PathBuf| p.to_string_lossy().into_owned();
+        (dir, path(old), path(new))
+    }
+
+    fn git_diff(args: &[&str]) -> Result<String, String> {
+        let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        args.insert(0, "git-diff".into());
+        let mut out = vec![];
+        run(&args, &mut out)
+            .map(|_| String::from_utf8(out).unwrap())
+            .map_err(|err| err.to_string())
+    }
+
+    const COMPARED: &str = "dimensions: 2x2\n\
+                            diff count: 1 (25.000%)\n\
+                            regions: 1\n  \
+                            1x1 at (0, 0), 1 pixels\n";
+
+    #[test]
+    fn should_diff_git_external_diff_arguments() {
+        let (dir, old, new) = setup("external");
+        let (hex, mode) = ("0123456", "100644");
+        let out = git_diff(&["a.png", &old, hex, mode, &new, hex, mode]).unwrap();
+        assert_eq!(
+            out,
+            format!("diff --pixelmatch a/a.png b/a.png\n{COMPARED}")
+        );
+
+        let out = git_diff(&["a.png", "/dev/null", hex, mode, &new, hex, mode]).unwrap();
+        assert_eq!(out, "diff --pixelmatch a/a.png b/a.png\nadded 2x2 image\n");
+
+        let diff_dir = dir.to_string_lossy().into_owned();
+        let args = [
+            "a.png",
+            &old,
+            hex,
+            mode,
+            &new,
+            hex,
+            mode,
+            "--diff-dir",
+            &diff_dir,
+        ];
+        let out = git_diff(&args).unwrap();
+        assert!(out.ends_with(&format!(
+            "diff image: {}\n",
+            dir.join("a.diff.png").display()
+        )));
+        assert!(dir.join("a.diff.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_diff_renamed_paths() {
+        let (dir, old, new) = setup("renamed");
+        let (hex, mode) = ("0123456", "100644");
+        let args = [
+            "a.png",
+            &old,
+            hex,
+            mode,
+            &new,
+            hex,
+            mode,
+            "b.png",
+            "similarity index 90%\nrename from a.png\nrename to b.png\n",
+        ];
+        let out = git_diff(&args).unwrap();
+        assert_eq!(
+            out,
+            format!("diff --pixelmatch a/a.png b/b.png\n{COMPARED}")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_report_unmerged_paths() {
+        assert_eq!(
+            git_diff(&["a.png"]).unwrap(),
+            "diff --pixelmatch a/a.png b/a.png\nunmerged\n"
+        );
+    }
+
+    #[test]
+    fn should_diff_difftool_arguments() {
+        let (dir, old, new) = setup("difftool");
+        let out = git_diff(&[&old, &new]).unwrap();
+        assert_eq!(
+            out,
+            format!("diff --pixelmatch a/{new} b/{new}\n{COMPARED}")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_fail_on_broken_images_and_wrong_arguments() {
+        let (dir, old, _) = setup("broken");
+        let broken = dir.join("broken.png");
+        fs::write(&broken, b"not a png").unwrap();
+        let broken = broken.to_string_lossy().into_owned();
+        let err = git_diff(&[&old, &broken]).unwrap_err();
+        assert!(err.starts_with(&format!("{broken}: ")));
+
+        assert!(git_diff(&[]).is_err());
+        assert!(git_diff(&["a", "b", "c"]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+//! the `pixelmatch` command line tool.
+//!
+//! ```text
+//! cargo install --path pixelmatch-cli
+//!
+//! pixelmatch check SCREENSHOTS BASELINES [--report report.html] [--watch]
+//! pixelmatch review [--dir snapshots] [--tui]
+//! pixelmatch approve (--all | GLOB...) [--dir snapshots] [--log FILE] [--approver NAME]
+//! pixelmatch reject (--all | GLOB...) [--dir snapshots] [--log FILE] [--approver NAME]
+//! pixelmatch serve [127.0.0.1:3000] [--max-concurrent 4] [--max-body BYTES]
+//! pixelmatch git-diff (GIT-DIFF-ARGS... | OLD NEW) [--diff-dir DIR]
+//! ```
+
+mod args;
+mod check;
+mod git_diff;
+mod report;
+mod review;
+mod serve;
+mod tui;
+
+use pixelmatch_snapshot::Error;
+use review::Decision;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "usage:
+  pixelmatch check SCREENSHOTS BASELINES [--report FILE] [--watch] [--threshold T]
+      compares each PNG under SCREENSHOTS with the one at the same path under BASELINES,
+      writing .actual.png and .diff.png next to baselines that don't match and an HTML report
+      to FILE; with --watch, compares changed screenshots again as soon as they are written
+  pixelmatch review [--dir DIR] [--threshold T]
+      lists the pending .actual.png files under DIR (snapshots) with their diff stats
+  pixelmatch review --tui [--dir DIR] [--graphics blocks|kitty|sixel] [--log FILE] [--approver NAME]
+      steps through the pending images in the terminal; a approves, r rejects,
+      n or right skips, p or left goes back and q quits
+  pixelmatch approve (--all | GLOB...) [--dir DIR] [--log FILE] [--approver NAME]
+      moves the matching pending images over their baselines
+  pixelmatch reject (--all | GLOB...) [--dir DIR] [--log FILE] [--approver NAME]
+      discards the matching pending images
+  pixelmatch serve [ADDR] [--max-concurrent N] [--max-body BYTES]
+      answers POST /compare on ADDR (127.0.0.1:3000) with the comparison of two images,
+      sent as raw rgba with ?width=W&height=H or as multipart PNG files img1 and img2,
+      with options as JSON in ?options= or an options part; ?diff=png returns the diff image
+  pixelmatch git-diff (GIT-DIFF-ARGS... | OLD NEW) [--diff-dir DIR]
+      compares two PNG files as git's diff.<driver>.command, or as a difftool with two files,
+      printing the diff count and regions and writing the diff image to DIR or $PIXELMATCH_DIFF_DIR
+
+globs match baseline paths relative to DIR; `*` stops at `/`, `**` doesn't.
+decisions are appended to FILE, DIR/approvals.log by default.
+";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = run(&args, &mut io::stdout().lock()) {
+        eprintln!("pixelmatch: {err}");
+        process::exit(1);
+    }
+}
+
+fn run<W: Write>(args: &[String], out: &mut W) -> Result<(), Error> {
+    let rest = args.get(1..).unwrap_or_default();
+    match args.first().map(String::as_str) {
+        Some("check") => check::check(rest, out),
+        Some("review") => review::review(rest, out),
+        Some("approve") => review::decide(rest, Decision::Approve, out),
+        Some("reject") => review::decide(rest, Decision::Reject, out),
+        Some("serve") => serve::serve_command(rest, out),
+        Some("git-diff") => git_diff::git_diff(rest, out),
+        None | Some("help" | "-h" | "--help") => Ok(write!(out, "{USAGE}")?),
+        Some(command) => Err(format!("unknown command `{command}`\n\n{USAGE}").into()),
+    }
+}
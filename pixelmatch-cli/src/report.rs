@@ -0,0 +1,184 @@
+use pixelmatch_snapshot::Outcome;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// the latest comparison of each screenshot, written as a static HTML page
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    screenshot: PathBuf,
+    baseline: PathBuf,
+    outcome: Result<Outcome, String>,
+}
+
+impl Report {
+    /// records the comparison of a screenshot, replacing an earlier one of the same name
+    pub fn update(
+        &mut self,
+        name: &str,
+        screenshot: &Path,
+        baseline: &Path,
+        outcome: Result<Outcome, String>,
+    ) {
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                screenshot: screenshot.to_path_buf(),
+                baseline: baseline.to_path_buf(),
+                outcome,
+            },
+        );
+    }
+
+    /// renders the page; with `refresh`, the browser reloads it every so many seconds
+    pub fn render(&self, refresh: Option<u32>) -> String {
+        let failed = self
+            .entries
+            .values()
+            .filter(|e| !matches!(e.outcome, Ok(Outcome::Matched)))
+            .count();
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        if let Some(seconds) = refresh {
+            html += &format!("<meta http-equiv=\"refresh\" content=\"{seconds}\">\n");
+        }
+        html += "<title>pixelmatch report</title>\n<style>\n\
+                 body { font-family: sans-serif; }\n\
+                 img { max-width: 32%; border: 1px solid #ccc; image-rendering: pixelated; }\n\
+                 .failed { color: #c0392b; }\n\
+                 </style>\n</head>\n<body>\n";
+        html += &format!(
+            "<h1>{failed} of {} screenshots differ</h1>\n",
+            self.entries.len()
+        );
+        for (name, entry) in &self.entries {
+            let (status, images) = describe(entry);
+            let class = if matches!(entry.outcome, Ok(Outcome::Matched)) {
+                ""
+            } else {
+                " class=\"failed\""
+            };
+            html += &format!(
+                "<section>\n<h2{class}>{}</h2>\n<p>{}</p>\n",
+                escape(name),
+                escape(&status)
+            );
+            for (label, path) in images {
+                html += &format!(
+                    "<img src=\"{}\" alt=\"{label}\" title=\"{label}\">\n",
+                    escape(&file_url(path))
+                );
+            }
+            html += "</section>\n";
+        }
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    /// writes the page to `path` through a temporary file, so that a browser reloading it
+    /// never sees it half written
+    pub fn write(&self, path: &Path, refresh: Option<u32>) -> io::Result<()> {
+        let tmp = path.with_extension("html.tmp");
+        fs::write(&tmp, self.render(refresh))?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// a status line and the images to show, baseline first
+fn describe(entry: &Entry) -> (String, Vec<(&'static str, &Path)>) {
+    let baseline = ("baseline", entry.baseline.as_path());
+    let screenshot = ("actual", entry.screenshot.as_path());
+    match &entry.outcome {
+        Ok(Outcome::Matched | Outcome::Updated) => ("matched".into(), vec![screenshot]),
+        Ok(Outcome::Missing { .. }) => ("no baseline".into(), vec![screenshot]),
+        Ok(Outcome::SizeMismatch { expected, .. }) => (
+            format!("the baseline is {}x{}", expected.0, expected.1),
+            vec![baseline, screenshot],
+        ),
+        Ok(Outcome::Mismatched {
+            diff_count,
+            diff_ratio,
+            diff,
+            ..
+        }) => (
+            format!("{diff_count} pixels differ ({:.3}%)", diff_ratio * 100.0),
+            vec![baseline, screenshot, ("diff", diff.as_path())],
+        ),
+        Err(err) => (format!("error: {err}"), vec![]),
+    }
+}
+
+/// a `file://` URL of a path, made absolute against the current directory
+fn file_url(path: &Path) -> String {
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+    for (i, component) in path.components().enumerate() {
+        let part = component.as_os_str().to_string_lossy();
+        if i > 0 && !url.ends_with('/') {
+            url.push('/');
+        }
+        for c in part.chars() {
+            match c {
+                'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '.' | '_' | '~' | '/' | ':' => {
+                    url.push(c)
+                }
+                c => {
+                    let mut buf = [0; 4];
+                    for b in c.encode_utf8(&mut buf).bytes() {
+                        url += &format!("%{b:02X}");
+                    }
+                }
+            }
+        }
+    }
+    url
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_render_the_latest_outcome_per_screenshot() {
+        let mut report = Report::default();
+        let (shot, baseline) = (Path::new("/s/a b.png"), Path::new("/b/a b.png"));
+        report.update("a b.png", shot, baseline, Ok(Outcome::Matched));
+        let html = report.render(Some(2));
+        assert!(html.contains("<meta http-equiv=\"refresh\" content=\"2\">"));
+        assert!(html.contains("<h1>0 of 1 screenshots differ</h1>"));
+        assert!(html.contains("<img src=\"file:///s/a%20b.png\" alt=\"actual\""));
+
+        report.update(
+            "a b.png",
+            shot,
+            baseline,
+            Ok(Outcome::Mismatched {
+                diff_count: 3,
+                diff_ratio: 0.5,
+                actual: "/b/a b.actual.png".into(),
+                diff: "/b/a b.diff.png".into(),
+            }),
+        );
+        report.update("<c>.png", shot, baseline, Err("broken".into()));
+        let html = report.render(None);
+        assert!(!html.contains("refresh"));
+        assert!(html.contains("<h1>2 of 2 screenshots differ</h1>"));
+        assert!(html.contains("<p>3 pixels differ (50.000%)</p>"));
+        assert!(html.contains("src=\"file:///b/a%20b.diff.png\""));
+        assert!(html.contains("<h2 class=\"failed\">&lt;c&gt;.png</h2>\n<p>error: broken</p>"));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::args::Args;
+use pixelmatch::PixelmatchOption;
+use pixelmatch_snapshot::review::{approve, glob_match, pending, reject, Pending};
+use pixelmatch_snapshot::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// a snapshot directory used when `--dir` is not given
+const DEFAULT_DIR: &str = "snapshots";
+/// a name of the approval log, under the snapshot directory unless `--log` is given
+const DEFAULT_LOG: &str = "approvals.log";
+
+/// what `approve` and `reject` do with the selected images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+/// `pixelmatch review`; lists the pending images with their diff stats,
+/// or steps through them with `--tui`
+pub fn review<W: Write>(args: &[String], out: &mut W) -> Result<(), Error> {
+    let args = Args::parse(
+        args,
+        &["tui"],
+        &["dir", "threshold", "graphics", "log", "approver"],
+    )?;
+    if args.flag("tui") {
+        return crate::tui::tui(&args, out);
+    }
+    let dir = snapshot_dir(&args);
+    let list = pending(&dir, options(&args)?)?;
+    for p in &list {
+        let stats = match p.stats {
+            Some(stats) => format!(
+                "{}x{}, {} pixels differ ({:.3}%)",
+                stats.dimensions.0,
+                stats.dimensions.1,
+                stats.diff_count,
+                stats.diff_ratio * 100.0
+            ),
+            None if p.baseline.exists() => "baseline of another size".into(),
+            None => "no baseline".into(),
+        };
+        writeln!(out, "{}: {stats}", relative(&dir, &p.baseline))?;
+    }
+    writeln!(out, "{} pending", list.len())?;
+    Ok(())
+}
+
+/// `pixelmatch approve` and `pixelmatch reject`; decides the pending images matching
+/// any of the globs, or all of them with `--all`
+pub fn decide<W: Write>(args: &[String], decision: Decision, out: &mut W) -> Result<(), Error> {
+    let args = Args::parse(args, &["all"], &["dir", "log", "approver"])?;
+    let globs = args.positionals();
+    if args.flag("all") != globs.is_empty() {
+        return Err("give either `--all` or globs of the images to decide".into());
+    }
+    let dir = snapshot_dir(&args);
+    let log = log_path(&args, &dir);
+    let approver = approver(&args);
+
+    let selected: Vec<Pending> = pending(&dir, None)?
+        .into_iter()
+        .filter(|p| {
+            let path = relative(&dir, &p.baseline);
+            args.flag("all") || globs.iter().any(|glob| glob_match(glob, &path))
+        })
+        .collect();
+    if selected.is_empty() && !args.flag("all") {
+        return Err("no pending image matches".into());
+    }
+    for p in &selected {
+        let verb = match decision {
+            Decision::Approve => {
+                approve(p, &approver, &log)?;
+                "approved"
+            }
+            Decision::Reject => {
+                reject(p, &approver, &log)?;
+                "rejected"
+            }
+        };
+        writeln!(out, "{verb} {}", relative(&dir, &p.baseline))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn snapshot_dir(args: &Args) -> PathBuf {
+    PathBuf::from(args.value("dir").unwrap_or(DEFAULT_DIR))
+}
+
+pub(crate) fn log_path(args: &Args, dir: &Path) -> PathBuf {
+    args.value("log")
+        .map_or_else(|| dir.join(DEFAULT_LOG), PathBuf::from)
+}
+
+/// `--approver`, or the user running the command
+pub(crate) fn approver(args: &Args) -> String {
+    args.value("approver")
+        .map(str::to_string)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".into())
+}
+
+pub(crate) fn options(args: &Args) -> Result<Option<PixelmatchOption>, Error> {
+    Ok(args
+        .parse_value("threshold")?
+        .map(|threshold| PixelmatchOption {
+            threshold,
+            ..PixelmatchOption::default()
+        }))
+}
+
+/// a path under the snapshot directory with `/` separators, as globs are matched against
+pub(crate) fn relative(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::run;
+    use pixelmatch_snapshot::{check, Outcome};
+    use std::fs;
+
+    #[test]
+    fn should_list_approve_and_reject_pending_images() {
+        let dir =
+            std::env::temp_dir().join(format!("pixelmatch-cli-review-{}", std::process::id()));
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        for name in ["buttons/primary", "buttons/secondary", "pages/home"] {
+            let baseline = dir.join(format!("{name}.png"));
+            check(&baseline, &white, (2, 2), None, true).unwrap();
+            check(&baseline, &changed, (2, 2), None, false).unwrap();
+        }
+        let dir_arg = dir.to_string_lossy().into_owned();
+        let run = |args: &[&str]| {
+            let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            args.extend(["--dir".into(), dir_arg.clone()]);
+            let mut out = vec![];
+            run(&args, &mut out).map(|_| String::from_utf8(out).unwrap())
+        };
+
+        let listed = run(&["review"]).unwrap();
+        assert!(listed.contains("buttons/primary.png: 2x2, 1 pixels differ (25.000%)\n"));
+        assert!(listed.ends_with("3 pending\n"));
+
+        assert!(run(&["approve"]).is_err());
+        assert!(run(&["approve", "missing/*"]).is_err());
+        assert_eq!(
+            run(&["approve", "buttons/*", "--approver", "carol"]).unwrap(),
+            "approved buttons/primary.png\napproved buttons/secondary.png\n"
+        );
+        assert_eq!(
+            run(&["reject", "--all", "--approver", "carol"]).unwrap(),
+            "rejected pages/home.png\n"
+        );
+
+        let primary = check(
+            &dir.join("buttons/primary.png"),
+            &changed,
+            (2, 2),
+            None,
+            false,
+        );
+        let home = check(&dir.join("pages/home.png"), &white, (2, 2), None, false);
+        assert_eq!(
+            (primary.unwrap(), home.unwrap()),
+            (Outcome::Matched, Outcome::Matched)
+        );
+        let log = fs::read_to_string(dir.join(DEFAULT_LOG)).unwrap();
+        assert_eq!(log.lines().count(), 3);
+        assert!(log.lines().all(|line| line.contains("\tcarol\t")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
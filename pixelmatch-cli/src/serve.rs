@@ -0,0 +1,812 @@
+//! `pixelmatch serve`; a local HTTP comparison server, for test runners written in other languages.
+//!
+//! ```text
+//! POST /compare?width=W&height=H[&options=JSON][&diff=png]
+//! body: img1 followed by img2, both raw rgba 24bit, W * H * 4 bytes each
+//!
+//! POST /compare[?diff=png]
+//! body: multipart/form-data with PNG files `img1` and `img2` and an optional `options` field
+//! ```
+//!
+//! options are a JSON object with the fields of `PixelmatchOption`, e.g.
+//! `{"threshold":0.2,"comparison_mode":"luminance","diff_color":[255,0,0,255]}`.
+//! responds with `{"diff_count":N,"shifted_count":M,"offset":[DX,DY],"hash_check":null}`,
+//! or with the diff image as a PNG and the counts in `X-Diff-Count` and `X-Shifted-Count`
+//! headers when `diff=png` is given.
+
+use crate::args::Args;
+use pixelmatch::hash::HashAlgorithm;
+use pixelmatch::mask::IgnoreMask;
+use pixelmatch::png_stream::{decode_png, encode_png};
+use pixelmatch::*;
+use pixelmatch_snapshot::Error;
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// an address listened on when none is given
+const DEFAULT_ADDR: &str = "127.0.0.1:3000";
+/// largest accepted request line or header
+const MAX_LINE: usize = 8 * 1024;
+
+/// how long a connection may stay idle while reading the request or writing the response
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// how much work the server takes on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// requests handled at once; more are answered with 503
+    pub max_concurrent: usize,
+    /// largest accepted body; each image may have at most `max_body / 8` pixels,
+    /// also when it is sent as a PNG
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            // two 4096x4096 images
+            max_body: 2 * 4096 * 4096 * 4,
+        }
+    }
+}
+
+/// `pixelmatch serve [ADDR]`; listens until killed
+pub fn serve_command<W: Write>(args: &[String], out: &mut W) -> Result<(), Error> {
+    let args = Args::parse(args, &[], &["max-concurrent", "max-body"])?;
+    let addr = match args.positionals() {
+        [] => DEFAULT_ADDR,
+        [addr] => addr.as_str(),
+        _ => return Err("give at most one address to listen on".into()),
+    };
+    let defaults = Limits::default();
+    let limits = Limits {
+        max_concurrent: args
+            .parse_value("max-concurrent")?
+            .unwrap_or(defaults.max_concurrent),
+        max_body: args.parse_value("max-body")?.unwrap_or(defaults.max_body),
+    };
+    let listener = TcpListener::bind(addr)?;
+    writeln!(out, "listening on http://{}", listener.local_addr()?)?;
+    out.flush()?;
+    serve(listener, limits);
+    Ok(())
+}
+
+fn serve(listener: TcpListener, limits: Limits) {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        let slot = Slot(in_flight.clone());
+        if slot.0.fetch_add(1, Ordering::SeqCst) >= limits.max_concurrent {
+            let _ = respond(
+                &stream,
+                "503 Service Unavailable",
+                "text/plain",
+                &[],
+                b"busy",
+            );
+            continue;
+        }
+        thread::spawn(move || {
+            let _slot = slot;
+            let timeouts = stream
+                .set_read_timeout(Some(TIMEOUT))
+                .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)));
+            if timeouts.is_err() {
+                return;
+            }
+            if let Err((status, message)) = handle(&stream, limits) {
+                let _ = respond(&stream, status, "text/plain", &[], message.as_bytes());
+            }
+        });
+    }
+}
+
+/// a request being handled; released when the handler returns or panics
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+type HttpError = (&'static str, String);
+
+fn bad_request(message: impl Into<String>) -> HttpError {
+    ("400 Bad Request", message.into())
+}
+
+fn handle(stream: &TcpStream, limits: Limits) -> Result<(), HttpError> {
+    let mut reader = BufReader::new(stream);
+    let request_line = read_line(&mut reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/compare" {
+        return Err(("404 Not Found", "not found".into()));
+    }
+    if method != "POST" {
+        return Err(("405 Method Not Allowed", "use POST".into()));
+    }
+
+    let (mut content_length, mut content_type, mut expect_continue) = (None, None, false);
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("expect") {
+                expect_continue = value.eq_ignore_ascii_case("100-continue");
+            }
+        }
+    }
+    let content_length =
+        content_length.ok_or(("411 Length Required", "content-length is required".into()))?;
+    if content_length > limits.max_body {
+        return Err((
+            "413 Payload Too Large",
+            format!("at most {} bytes", limits.max_body),
+        ));
+    }
+
+    let params: Vec<(String, String)> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| Ok((percent_decode(key)?, percent_decode(value)?)))
+        .collect::<Result<_, HttpError>>()?;
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let boundary = content_type
+        .as_deref()
+        .and_then(|t| t.strip_prefix("multipart/form-data"))
+        .map(|rest| {
+            rest.split(';')
+                .filter_map(|p| p.trim().strip_prefix("boundary="))
+                .map(|b| b.trim_matches('"').to_string())
+                .next()
+                .ok_or_else(|| bad_request("multipart boundary is missing"))
+        })
+        .transpose()?;
+
+    // raw images must add up to the body before it is read
+    let raw_dimensions = if boundary.is_none() {
+        let number = |name: &str| -> Result<u32, HttpError> {
+            param(name)
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| bad_request(format!("{name} is required")))
+        };
+        let dimensions = (number("width")?, number("height")?);
+        let image_len = (dimensions.0 as usize)
+            .checked_mul(dimensions.1 as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(|| bad_request("image is too large"))?;
+        if image_len.checked_mul(2) != Some(content_length) {
+            return Err(bad_request(format!(
+                "expected two images of {image_len} bytes"
+            )));
+        }
+        Some(dimensions)
+    } else {
+        None
+    };
+
+    if expect_continue {
+        let mut stream = stream;
+        stream
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .map_err(|err| bad_request(err.to_string()))?;
+    }
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| bad_request(err.to_string()))?;
+
+    let max_pixels = limits.max_body / 8;
+    let ((img1, img2, dimensions), options) = match (raw_dimensions, boundary) {
+        (Some(dimensions), _) => {
+            let img2 = body.split_off(content_length / 2);
+            (
+                (body, img2, dimensions),
+                param("options").map(str::to_string),
+            )
+        }
+        (None, Some(boundary)) => {
+            let parts = parse_multipart(&body, &boundary)?;
+            let part = |name: &str| parts.iter().find(|(n, _)| n == name).map(|(_, data)| *data);
+            let png = |name: &str| -> Result<(Vec<u8>, (u32, u32)), HttpError> {
+                let data = part(name).ok_or_else(|| bad_request(format!("{name} is required")))?;
+                let (image, dimensions) = decode_png(data)
+                    .map_err(|err| bad_request(format!("{name} is not a PNG image: {err}")))?;
+                if dimensions.0 as usize * dimensions.1 as usize > max_pixels {
+                    return Err(("413 Payload Too Large", format!("{name} is too large")));
+                }
+                Ok((image, dimensions))
+            };
+            let ((img1, dimensions1), (img2, dimensions2)) = (png("img1")?, png("img2")?);
+            if dimensions1 != dimensions2 {
+                return Err(bad_request("img1 and img2 are of different sizes"));
+            }
+            let options = part("options")
+                .map(|data| String::from_utf8(data.to_vec()))
+                .transpose()
+                .map_err(|_| bad_request("options are not UTF-8"))?;
+            ((img1, img2, dimensions1), options)
+        }
+        (None, None) => unreachable!("raw requests have dimensions"),
+    };
+
+    let mut options = match options {
+        Some(options) => {
+            let json: Value = serde_json::from_str(&options)
+                .map_err(|err| bad_request(format!("invalid options: {err}")))?;
+            parse_options(&json).map_err(|err| bad_request(format!("invalid options: {err}")))?
+        }
+        None => PixelmatchOption::default(),
+    };
+    let want_png = param("diff") == Some("png");
+    if want_png {
+        // the hash check may skip the diff image
+        options.hash_check = None;
+    }
+
+    let result = pixelmatch(&img1, &img2, dimensions, Some(options))
+        .map_err(|err| bad_request(err.to_string()))?;
+    let counts = [
+        ("X-Diff-Count", result.diff_count.to_string()),
+        ("X-Shifted-Count", result.shifted_count.to_string()),
+    ];
+    let sent = if want_png {
+        let mut png = vec![];
+        encode_png(&mut png, &result.diff_image, dimensions)
+            .map_err(|err| ("500 Internal Server Error", err.to_string()))?;
+        respond(stream, "200 OK", "image/png", &counts, &png)
+    } else {
+        let json = json!({
+            "diff_count": result.diff_count,
+            "shifted_count": result.shifted_count,
+            "offset": [result.offset.0, result.offset.1],
+            "hash_check": result.hash_check.map(|check| json!({
+                "distance": check.distance,
+                "verdict": match check.verdict {
+                    HashVerdict::Identical => "identical",
+                    HashVerdict::Similar => "similar",
+                    HashVerdict::GrosslyDifferent => "grossly_different",
+                },
+            })),
+        });
+        respond(
+            stream,
+            "200 OK",
+            "application/json",
+            &counts,
+            json.to_string().as_bytes(),
+        )
+    };
+    sent.map_err(|err| ("500 Internal Server Error", err.to_string()))
+}
+
+/// reads `PixelmatchOption` from a JSON object with the same field names; enums are
+/// snake_case names, or single-field objects for variants with data, colors are arrays
+/// of channels and `ignore_mask` is a base64 encoded `IgnoreMask::to_rle`.
+/// fields left out keep their defaults.
+fn parse_options(json: &Value) -> Result<PixelmatchOption, String> {
+    let mut options = PixelmatchOption::default();
+    for (key, value) in object("options", json, &[])? {
+        let key = key.as_str();
+        match key {
+            "threshold" => {
+                options.threshold = value
+                    .as_f64()
+                    .ok_or_else(|| format!("`{key}` must be a number"))?
+                    as f32
+            }
+            "include_anti_alias" => options.include_anti_alias = boolean(key, value)?,
+            "linear_light" => options.linear_light = boolean(key, value)?,
+            "comparison_mode" => {
+                options.comparison_mode = match value {
+                    Value::String(name) if name == "yiq" => ComparisonMode::Yiq,
+                    Value::String(name) if name == "luminance" => ComparisonMode::Luminance,
+                    Value::String(_) => return Err(format!("unknown `{key}`")),
+                    _ => {
+                        let tagged = object(key, value, &["channel_tolerance"])?;
+                        match tagged.get("channel_tolerance") {
+                            Some(tolerance) if tagged.len() == 1 => {
+                                ComparisonMode::ChannelTolerance(integer(key, tolerance)?)
+                            }
+                            _ => return Err(format!("unknown `{key}`")),
+                        }
+                    }
+                }
+            }
+            "backdrop" => {
+                options.backdrop = match value {
+                    Value::Array(_) => Backdrop::Solid(rgb(key, value)?),
+                    Value::String(name) if name == "white_and_black" => Backdrop::WhiteAndBlack,
+                    _ => {
+                        let tagged = object(key, value, &["checkerboard"])?;
+                        let board = tagged
+                            .get("checkerboard")
+                            .ok_or_else(|| format!("unknown `{key}`"))?;
+                        let fields = object(key, board, &["size", "light", "dark"])?;
+                        let field = |name: &str| {
+                            fields
+                                .get(name)
+                                .ok_or_else(|| format!("`{key}` needs `{name}`"))
+                        };
+                        Backdrop::Checkerboard {
+                            size: integer(key, field("size")?)?,
+                            light: rgb(key, field("light")?)?,
+                            dark: rgb(key, field("dark")?)?,
+                        }
+                    }
+                }
+            }
+            "img1_alpha_mode" => options.img1_alpha_mode = alpha_mode(key, value)?,
+            "img2_alpha_mode" => options.img2_alpha_mode = alpha_mode(key, value)?,
+            "diff_color" => options.diff_color = rgba(key, value)?,
+            "anti_aliased_color" => options.anti_aliased_color = rgba(key, value)?,
+            "shifted_color" => options.shifted_color = rgba(key, value)?,
+            "shift_radius" => options.shift_radius = integer(key, value)?,
+            "hash_check" if value.is_null() => options.hash_check = None,
+            "hash_check" => {
+                let fields = object(
+                    key,
+                    value,
+                    &["algorithm", "identical_distance", "gross_distance"],
+                )?;
+                let mut check = HashCheck::default();
+                for (field, value) in fields {
+                    match field.as_str() {
+                        "algorithm" => {
+                            check.algorithm = match value.as_str() {
+                                Some("average") => HashAlgorithm::Average,
+                                Some("difference") => HashAlgorithm::Difference,
+                                Some("perceptual") => HashAlgorithm::Perceptual,
+                                _ => return Err(format!("unknown `{key}.algorithm`")),
+                            }
+                        }
+                        "identical_distance" => check.identical_distance = integer(key, value)?,
+                        _ => check.gross_distance = integer(key, value)?,
+                    }
+                }
+                options.hash_check = Some(check);
+            }
+            "alignment" if value.is_null() => options.alignment = None,
+            "alignment" => {
+                let fields = object(key, value, &["max_offset", "border"])?;
+                let mut alignment = Alignment::default();
+                for (field, value) in fields {
+                    match field.as_str() {
+                        "max_offset" => alignment.max_offset = integer(key, value)?,
+                        _ => {
+                            alignment.border = match value.as_str() {
+                                Some("ignore") => BorderPolicy::Ignore,
+                                Some("count") => BorderPolicy::Count,
+                                _ => return Err(format!("unknown `{key}.border`")),
+                            }
+                        }
+                    }
+                }
+                options.alignment = Some(alignment);
+            }
+            "ignore_mask" if value.is_null() => options.ignore_mask = None,
+            "ignore_mask" => {
+                let rle = value
+                    .as_str()
+                    .and_then(decode_base64)
+                    .ok_or_else(|| format!("`{key}` must be a base64 string"))?;
+                let mask = IgnoreMask::from_rle(&rle).map_err(|err| format!("`{key}`: {err}"))?;
+                options.ignore_mask = Some(mask);
+            }
+            _ => return Err(format!("unknown field `{key}`")),
+        }
+    }
+    Ok(options)
+}
+
+/// a JSON object whose fields are all in `allowed`, or any fields if `allowed` is empty
+fn object<'a>(
+    key: &str,
+    value: &'a Value,
+    allowed: &[&str],
+) -> Result<&'a Map<String, Value>, String> {
+    let fields = value
+        .as_object()
+        .ok_or_else(|| format!("`{key}` must be an object"))?;
+    match fields
+        .keys()
+        .find(|field| !allowed.is_empty() && !allowed.contains(&field.as_str()))
+    {
+        Some(field) => Err(format!("unknown field `{key}.{field}`")),
+        None => Ok(fields),
+    }
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("`{key}` must be true or false"))
+}
+
+fn integer<T: TryFrom<u64>>(key: &str, value: &Value) -> Result<T, String> {
+    value
+        .as_u64()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("`{key}` is not an integer in range"))
+}
+
+fn channels<const N: usize>(key: &str, value: &Value) -> Result<[u8; N], String> {
+    let channels = value
+        .as_array()
+        .filter(|channels| channels.len() == N)
+        .ok_or_else(|| format!("`{key}` must be an array of {N} channels"))?;
+    let mut out = [0; N];
+    for (out, channel) in out.iter_mut().zip(channels) {
+        *out = integer(key, channel)?;
+    }
+    Ok(out)
+}
+
+fn rgb(key: &str, value: &Value) -> Result<Rgb, String> {
+    let [r, g, b] = channels(key, value)?;
+    Ok((r, g, b))
+}
+
+fn rgba(key: &str, value: &Value) -> Result<Rgba, String> {
+    let [r, g, b, a] = channels(key, value)?;
+    Ok((r, g, b, a))
+}
+
+fn alpha_mode(key: &str, value: &Value) -> Result<AlphaMode, String> {
+    match value.as_str() {
+        Some("straight") => Ok(AlphaMode::Straight),
+        Some("premultiplied") => Ok(AlphaMode::Premultiplied),
+        Some("ignore") => Ok(AlphaMode::Ignore),
+        _ => Err(format!("unknown `{key}`")),
+    }
+}
+
+/// the named parts of a multipart/form-data body
+fn parse_multipart<'a>(
+    body: &'a [u8],
+    boundary: &str,
+) -> Result<Vec<(String, &'a [u8])>, HttpError> {
+    let invalid = || bad_request("invalid multipart body");
+    let delimiter = format!("--{boundary}");
+    let mut rest = &body[find(body, delimiter.as_bytes()).ok_or_else(invalid)? + delimiter.len()..];
+    let mut parts = vec![];
+    // each part starts after a delimiter line and ends before the next one
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n").ok_or_else(invalid)?;
+        let headers_end = find(rest, b"\r\n\r\n").ok_or_else(invalid)?;
+        let headers = String::from_utf8_lossy(&rest[..headers_end]);
+        let name = headers
+            .lines()
+            .filter(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("content-disposition:")
+            })
+            .flat_map(|line| line.split(';'))
+            .filter_map(|p| p.trim().strip_prefix("name="))
+            .map(|name| name.trim_matches('"').to_string())
+            .next()
+            .ok_or_else(|| bad_request("a multipart part has no name"))?;
+        rest = &rest[headers_end + 4..];
+        let end = find(rest, format!("\r\n{delimiter}").as_bytes()).ok_or_else(invalid)?;
+        parts.push((name, &rest[..end]));
+        rest = &rest[end + 2 + delimiter.len()..];
+    }
+    Ok(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// decodes `%XX` escapes and `+` of a query string component
+fn percent_decode(text: &str) -> Result<String, HttpError> {
+    let invalid = || bad_request("invalid query string");
+    let mut out = vec![];
+    let mut bytes = text.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = [bytes.next(), bytes.next()];
+                let hex = match hex {
+                    [Some(h), Some(l)] => [h, l],
+                    _ => return Err(invalid()),
+                };
+                let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            }
+            b => out.push(b),
+        }
+    }
+    String::from_utf8(out).map_err(|_| invalid())
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for &c in text {
+        bits = bits << 6 | value(c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<String, HttpError> {
+    let mut line = vec![];
+    reader
+        .take(MAX_LINE as u64)
+        .read_until(b'\n', &mut line)
+        .map_err(|err| bad_request(err.to_string()))?;
+    if !line.ends_with(b"\n") {
+        return Err((
+            "431 Request Header Fields Too Large",
+            "line too long".into(),
+        ));
+    }
+    String::from_utf8(line)
+        .map(|line| line.trim_end().to_string())
+        .map_err(|_| bad_request("invalid header"))
+}
+
+fn respond(
+    mut stream: &TcpStream,
+    status: &str,
+    content_type: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        body.len()
+    )?;
+    for (name, value) in headers {
+        write!(stream, "{name}: {value}\r\n")?;
+    }
+    stream.write_all(b"\r\n")?;
+    stream.write_all(body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn start(limits: Limits) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, limits));
+        addr
+    }
+
+    fn post(addr: std::net::SocketAddr, query: &str, headers: &str, body: &[u8]) -> String {
+        String::from_utf8_lossy(&post_bytes(addr, query, headers, body)).into_owned()
+    }
+
+    fn post_bytes(addr: std::net::SocketAddr, query: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST /compare?{query} HTTP/1.1\r\n{headers}Content-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        response
+    }
+
+    fn json_body(response: &str) -> Value {
+        serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn should_compare_posted_images() {
+        let addr = start(Limits::default());
+        let img1 = [255; 2 * 2 * 4];
+        let mut img2 = img1;
+        img2[..4].copy_from_slice(&[0, 0, 0, 255]);
+        let response = post(addr, "width=2&height=2", "", &[img1, img2].concat());
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("X-Diff-Count: 1"));
+        assert_eq!(
+            json_body(&response),
+            json!({"diff_count": 1, "shifted_count": 0, "offset": [0, 0], "hash_check": null})
+        );
+
+        // more failing requests than slots don't leave the server busy
+        for _ in 0..Limits::default().max_concurrent + 1 {
+            let response = post(addr, "width=4294967295&height=4294967295", "", &[0; 8]);
+            assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+        }
+        let response = post(addr, "width=2&height=2", "", &[img1, img1].concat());
+        assert_eq!(json_body(&response)["diff_count"], 0);
+    }
+
+    #[test]
+    fn should_take_options_as_json() {
+        let addr = start(Limits::default());
+        // pure red and a gray of the same brightness
+        let img1 = [255, 0, 0, 255];
+        let img2 = [76, 76, 76, 255];
+        let body = [img1, img2].concat();
+        let response = post(addr, "width=1&height=1", "", &body);
+        assert_eq!(json_body(&response)["diff_count"], 1);
+
+        let options = "%7B%22comparison_mode%22%3A%22luminance%22%7D";
+        let response = post(
+            addr,
+            &format!("width=1&height=1&options={options}"),
+            "",
+            &body,
+        );
+        assert_eq!(json_body(&response)["diff_count"], 0);
+
+        let query = "width=1&height=1&options=%7B%22thresold%22%3A1%7D";
+        let response = post(addr, query, "", &body);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(response.ends_with("invalid options: unknown field `thresold`"));
+    }
+
+    #[test]
+    fn should_compare_multipart_png_images() {
+        let addr = start(Limits::default());
+        let png = |image: &[u8]| {
+            let mut png = vec![];
+            encode_png(&mut png, image, (2, 1)).unwrap();
+            png
+        };
+        let white = [255; 2 * 4];
+        let mut changed = white;
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        let part = |name: &str, data: &[u8]| {
+            [
+                format!("--XyZ\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{name}.png\"\r\nContent-Type: image/png\r\n\r\n").as_bytes(),
+                data,
+                b"\r\n",
+            ]
+            .concat()
+        };
+        let body = [
+            part("img1", &png(&white)),
+            part("img2", &png(&changed)),
+            part("options", br#"{"diff_color": [0, 0, 255, 255]}"#),
+            b"--XyZ--\r\n".to_vec(),
+        ]
+        .concat();
+        let headers = "Content-Type: multipart/form-data; boundary=XyZ\r\n";
+        let response = post(addr, "", headers, &body);
+        assert_eq!(json_body(&response)["diff_count"], 1);
+
+        let response = post_bytes(addr, "diff=png", headers, &body);
+        let png_start = find(&response, b"\r\n\r\n").unwrap() + 4;
+        assert!(String::from_utf8_lossy(&response[..png_start]).contains("Content-Type: image/png"));
+        let (diff, dimensions) = decode_png(&response[png_start..]).unwrap();
+        assert_eq!(dimensions, (2, 1));
+        assert_eq!(diff[..4], [0, 0, 255, 255]);
+
+        let response = post(addr, "", headers, &body[..body.len() / 2]);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn should_reject_bodies_over_the_limit() {
+        let addr = start(Limits {
+            max_concurrent: 1,
+            max_body: 16,
+        });
+        let response = post(addr, "width=2&height=2", "", &[255; 32]);
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large"));
+        let response = post(addr, "width=1&height=1", "", &[255; 8]);
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn should_read_every_option() {
+        // one ignored pixel
+        let mask = IgnoreMask::from_rle(b"PMSK\x01\0\0\0\x01\0\0\0\0\x01").unwrap();
+        assert_eq!(mask.ignored_count(), 1);
+        let json = json!({
+            "threshold": 0.25,
+            "include_anti_alias": true,
+            "comparison_mode": {"channel_tolerance": 3},
+            "backdrop": {"checkerboard": {"size": 4, "light": [255, 255, 255], "dark": [204, 204, 204]}},
+            "img1_alpha_mode": "premultiplied",
+            "img2_alpha_mode": "ignore",
+            "linear_light": true,
+            "diff_color": [1, 2, 3, 4],
+            "anti_aliased_color": [5, 6, 7, 8],
+            "shifted_color": [9, 10, 11, 12],
+            "shift_radius": 2,
+            "hash_check": {"algorithm": "perceptual", "gross_distance": 30},
+            "alignment": {"max_offset": 8, "border": "count"},
+            "ignore_mask": "UE1TSwEAAAABAAAAAAE=",
+        });
+        let options = parse_options(&json).unwrap();
+        assert_eq!(
+            options,
+            PixelmatchOption {
+                threshold: 0.25,
+                include_anti_alias: true,
+                comparison_mode: ComparisonMode::ChannelTolerance(3),
+                backdrop: Backdrop::Checkerboard {
+                    size: 4,
+                    light: (255, 255, 255),
+                    dark: (204, 204, 204)
+                },
+                img1_alpha_mode: AlphaMode::Premultiplied,
+                img2_alpha_mode: AlphaMode::Ignore,
+                linear_light: true,
+                diff_color: (1, 2, 3, 4),
+                anti_aliased_color: (5, 6, 7, 8),
+                shifted_color: (9, 10, 11, 12),
+                shift_radius: 2,
+                hash_check: Some(HashCheck {
+                    algorithm: HashAlgorithm::Perceptual,
+                    gross_distance: 30,
+                    ..HashCheck::default()
+                }),
+                alignment: Some(Alignment {
+                    max_offset: 8,
+                    border: BorderPolicy::Count
+                }),
+                ignore_mask: Some(mask),
+            }
+        );
+
+        let error = |json: Value| parse_options(&json).unwrap_err();
+        assert_eq!(error(json!([])), "`options` must be an object");
+        assert_eq!(
+            error(json!({"diff_color": [1, 2, 3]})),
+            "`diff_color` must be an array of 4 channels"
+        );
+        assert_eq!(
+            error(json!({"shift_radius": -1})),
+            "`shift_radius` is not an integer in range"
+        );
+        assert_eq!(
+            error(json!({"alignment": {"offset": 1}})),
+            "unknown field `alignment.offset`"
+        );
+        assert_eq!(
+            error(json!({"comparison_mode": "rgb"})),
+            "unknown `comparison_mode`"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::args::Args;
+use crate::review::{approver, log_path, options, snapshot_dir};
+use crossterm::tty::IsTty;
+use crossterm::{cursor, execute, terminal};
+use pixelmatch_snapshot::terminal::{review_pending, Graphics, Preview};
+use pixelmatch_snapshot::Error;
+use std::io::{self, Write};
+
+/// `pixelmatch review --tui`; steps through the pending images on the alternate screen,
+/// taking a decision per key press
+pub fn tui<W: Write>(args: &Args, out: &mut W) -> Result<(), Error> {
+    if !io::stdin().is_tty() || !io::stdout().is_tty() {
+        return Err("`--tui` needs a terminal".into());
+    }
+    let dir = snapshot_dir(args);
+    let graphics = args
+        .parse_value("graphics")?
+        .unwrap_or_else(Graphics::detect);
+    let (width, _) = terminal::size()?;
+    let columns = match graphics {
+        // three previews side by side, one space apart
+        Graphics::HalfBlocks => (width.saturating_sub(2) / 3).max(1) as u32,
+        _ => width as u32,
+    };
+    let preview = Preview { graphics, columns };
+
+    let summary = {
+        let _screen = RawScreen::enter()?;
+        review_pending(
+            &dir,
+            &approver(args),
+            &log_path(args, &dir),
+            options(args)?,
+            preview,
+            io::stdin().lock(),
+            Crlf(io::stdout().lock()),
+        )?
+    };
+    writeln!(
+        out,
+        "approved {}, rejected {}, skipped {}",
+        summary.approved, summary.rejected, summary.skipped
+    )?;
+    Ok(())
+}
+
+/// raw mode on the alternate screen; restored when dropped, also on errors and panics
+struct RawScreen;
+
+impl RawScreen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let screen = RawScreen;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(screen)
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// writes `\n` as `\r\n`, since a line feed in raw mode keeps the cursor in its column
+struct Crlf<W>(W);
+
+impl<W: Write> Write for Crlf<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+            if i > 0 {
+                self.0.write_all(b"\r\n")?;
+            }
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_return_the_carriage_on_line_feeds() {
+        let mut out = Crlf(vec![]);
+        write!(out, "a\nb\n\nc").unwrap();
+        assert_eq!(out.0, b"a\r\nb\r\n\r\nc");
+    }
+}
@@ -2,6 +2,7 @@ export const createDefaultOptions = () => {
   return {
     includeAntiAlias: false,
     threshold: 0.1,
+    comparisonMode: "yiq",
     diffColor: [255, 119, 119, 255],
     antiAliasedColor: [243, 156, 18, 255],
   };
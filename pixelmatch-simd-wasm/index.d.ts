@@ -19,6 +19,7 @@ export function pixelmatch(
 export type PixelmatchOptions = {
   includeAntiAlias: boolean;
   threshold: number;
+  comparisonMode: "yiq" | "luminance";
   diffColor: [number, number, number, number];
   antiAliasedColor: [number, number, number, number];
 };
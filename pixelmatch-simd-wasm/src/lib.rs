@@ -1,3 +1,6 @@
+//! the SIMD backend only exists on wasm32; on other targets the crate is empty, so that
+//! `cargo test --workspace` builds without a wasm target
+#![cfg(target_arch = "wasm32")]
 #![no_std]
 use core::arch::wasm32::*;
 use core::cmp::{max, min};
@@ -6,6 +9,7 @@ use wasm_bindgen::prelude::*;
 struct PixelmatchOption {
     pub include_anti_alias: bool,
     pub threshold: f32,
+    pub only_brightness: bool,
     pub diff_color: Rgba,
     pub anti_aliased_color: Rgba,
 }
@@ -38,6 +42,7 @@ pub fn pixelmatch(
     anti_aliased_color_g: u8,
     anti_aliased_color_b: u8,
     anti_aliased_color_a: u8,
+    only_brightness: bool,
 ) -> isize {
     if img1.len() != img2.len() {
         return IMAGE_LENGTH_ERROR;
@@ -49,6 +54,7 @@ pub fn pixelmatch(
     let options = PixelmatchOption {
         include_anti_alias,
         threshold,
+        only_brightness,
         diff_color: Rgba(diff_color_r, diff_color_g, diff_color_b, diff_color_a),
         anti_aliased_color: Rgba(
             anti_aliased_color_r,
@@ -60,11 +66,36 @@ pub fn pixelmatch(
 
     // maximum acceptable square distance between two colors;
     // 35215 is the maximum possible value for the YIQ difference metric
+    // and 255 is the maximum possible brightness difference
     let threshold = options.threshold;
-    let max_delta = 35215.0 * threshold * threshold;
+    let max_delta = if options.only_brightness {
+        (255.0 * threshold) * (255.0 * threshold)
+    } else {
+        35215.0 * threshold * threshold
+    };
     let mut diff_count = 0;
 
+    let stride = (width * 4) as usize;
     for y in 0..height {
+        // identical rows are similar everywhere; skip the per-pixel color distance
+        let row = y as usize * stride..(y as usize + 1) * stride;
+        if matches!((img1.get(row.clone()), img2.get(row)), (Some(row1), Some(row2)) if row1 == row2)
+        {
+            for x in 0..width {
+                let pos = ((y * width + x) * 4) as usize;
+                let rgba1 = unsafe {
+                    f32x4(
+                        *img1.get_unchecked(pos) as f32,
+                        *img1.get_unchecked(pos + 1) as f32,
+                        *img1.get_unchecked(pos + 2) as f32,
+                        *img1.get_unchecked(pos + 3) as f32,
+                    )
+                };
+                let y = blend_u8(gray_pixel(rgba1) as u8, 0.1);
+                draw_pixel(out, pos, &Rgba(y, y, y, 255));
+            }
+            continue;
+        }
         for x in 0..width {
             let pos = ((y * width + x) * 4) as usize;
 
@@ -86,8 +117,8 @@ pub fn pixelmatch(
                 )
             };
 
-            // squared YUV distance between colors at this pixel position
-            let delta = color_delta(rgba1, rgba2, false);
+            // squared YUV (or brightness) distance between colors at this pixel position
+            let delta = squared_delta(rgba1, rgba2, options.only_brightness);
             if f32::abs(delta) > max_delta {
                 // check it's a real rendering difference or just anti-aliasing
                 if !options.include_anti_alias
@@ -122,6 +153,15 @@ fn sum_rgb(v: v128) -> f32 {
     f32x4_extract_lane::<0>(v) + f32x4_extract_lane::<1>(v) + f32x4_extract_lane::<2>(v)
 }
 
+fn squared_delta(rgba1: v128, rgba2: v128, only_brightness: bool) -> f32 {
+    if only_brightness {
+        let y = color_delta(rgba1, rgba2, true);
+        y * y
+    } else {
+        color_delta(rgba1, rgba2, false)
+    }
+}
+
 // calculate color difference according to the paper "Measuring perceived color difference
 // using YIQ NTSC transmission color space in mobile applications" by Y. Kotsarenko and F. Ramos
 fn color_delta(rgba1: v128, rgba2: v128, only_brightness: bool) -> f32 {
@@ -363,7 +403,7 @@ mod test {
         let mut out: [u8; 16] = [0; 16];
 
         let result = pixelmatch(
-            &img1, &img2, &mut out, 2, 2, true, 0.1, 255, 119, 119, 255, 243, 156, 18, 255,
+            &img1, &img2, &mut out, 2, 2, true, 0.1, 255, 119, 119, 255, 243, 156, 18, 255, false,
         );
         assert_eq!(result, 1);
         assert_eq!(
@@ -372,4 +412,68 @@ mod test {
         );
         assert!(true);
     }
+
+    #[wasm_bindgen_test]
+    fn should_match_core_luminance_mode_on_hue_change() {
+        // pure red and a gray of the same brightness, then a white to black change
+        let img1: [u8; 16] = [
+            255, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+        ];
+        let img2: [u8; 16] = [76, 76, 76, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let mut out: [u8; 16] = [0; 16];
+
+        let result = pixelmatch(
+            &img1, &img2, &mut out, 2, 2, false, 0.1, 255, 119, 119, 255, 243, 156, 18, 255, true,
+        );
+        let core = ::pixelmatch::pixelmatch(
+            &img1,
+            &img2,
+            (2, 2),
+            Some(::pixelmatch::PixelmatchOption {
+                comparison_mode: ::pixelmatch::ComparisonMode::Luminance,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(result, 1);
+        assert_eq!(result as usize, core.diff_count);
+        assert_eq!(out[..], core.diff_image[..]);
+    }
+
+    #[wasm_bindgen_test]
+    fn should_match_core_diff_image_when_rows_are_skipped() {
+        // rows 0, 2 and 3 are identical and skipped, semi-transparent pixels included;
+        // row 1 differs in one pixel
+        let (w, h) = (4, 4);
+        let mut img1 = [0u8; 64];
+        for (i, pixel) in img1.chunks_mut(4).enumerate() {
+            pixel.copy_from_slice(&[
+                (i * 37) as u8,
+                (i * 11) as u8,
+                (i * 5) as u8,
+                (255 - i * 9) as u8,
+            ]);
+        }
+        let mut img2 = img1;
+        img2[(w + 2) * 4..(w + 3) * 4].copy_from_slice(&[0, 0, 0, 255]);
+        let mut out = [0u8; 64];
+
+        let result = pixelmatch(
+            &img1, &img2, &mut out, w as u32, h as u32, true, 0.1, 255, 119, 119, 255, 243, 156,
+            18, 255, false,
+        );
+        let core = ::pixelmatch::pixelmatch(
+            &img1,
+            &img2,
+            (w as u32, h as u32),
+            Some(::pixelmatch::PixelmatchOption {
+                include_anti_alias: true,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(result, 1);
+        assert_eq!(result as usize, core.diff_count);
+        assert_eq!(out[..], core.diff_image[..]);
+    }
 }
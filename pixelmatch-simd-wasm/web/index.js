@@ -26,7 +26,8 @@ export const pixelmatch = (img1, img2, w, h, opts = {}) => {
     antiAliasedColor[0],
     antiAliasedColor[1],
     antiAliasedColor[2],
-    antiAliasedColor[3]
+    antiAliasedColor[3],
+    (opts.comparisonMode ?? defaultOptions.comparisonMode) === "luminance"
   );
 
   if (countOrError < 0) {
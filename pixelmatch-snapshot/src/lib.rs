@@ -0,0 +1,318 @@
+pub mod review;
+pub mod terminal;
+pub mod watch;
+
+use pixelmatch::mask::IgnoreMask;
+use pixelmatch::png_stream::{decode_png, encode_png};
+use pixelmatch::{pixelmatch, PixelmatchOption};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// an environment variable that, when set to `1`, writes or replaces baselines instead of failing
+pub const UPDATE_ENV: &str = "PIXELMATCH_UPDATE";
+
+/// an error of check; reading or writing a PNG, or the comparison itself
+pub type Error = Box<dyn std::error::Error>;
+
+/// asserts that an image matches the named snapshot under `snapshots/` of the crate root.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pixelmatch_snapshot::assert_image_snapshot;
+///
+/// let image = vec![255; 2 * 2 * 4];
+/// assert_image_snapshot!("white", image, (2, 2));
+/// ```
+#[macro_export]
+macro_rules! assert_image_snapshot {
+    ($name:expr, $image:expr, $dimensions:expr $(,)?) => {
+        $crate::assert_image_snapshot!($name, $image, $dimensions, None)
+    };
+    ($name:expr, $image:expr, $dimensions:expr, $options:expr $(,)?) => {
+        $crate::assert_matches(
+            &::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("snapshots")
+                .join(format!("{}.png", $name)),
+            ::core::convert::AsRef::<[u8]>::as_ref(&$image),
+            $dimensions,
+            $options,
+        )
+    };
+}
+
+/// asserts that an image matches the baseline PNG at a path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pixelmatch_snapshot::assert_image_matches;
+///
+/// let image = vec![255; 2 * 2 * 4];
+/// assert_image_matches!(image, (2, 2), "tests/white.png");
+/// ```
+#[macro_export]
+macro_rules! assert_image_matches {
+    ($image:expr, $dimensions:expr, $baseline:expr $(,)?) => {
+        $crate::assert_image_matches!($image, $dimensions, $baseline, None)
+    };
+    ($image:expr, $dimensions:expr, $baseline:expr, $options:expr $(,)?) => {
+        $crate::assert_matches(
+            ::std::path::Path::new(&$baseline),
+            ::core::convert::AsRef::<[u8]>::as_ref(&$image),
+            $dimensions,
+            $options,
+        )
+    };
+}
+
+/// a result of check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// the image matches the baseline
+    Matched,
+    /// the baseline was written, because it was missing or updating was requested
+    Updated,
+    /// there is no baseline; the image was written to `actual`
+    Missing { actual: PathBuf },
+    /// the baseline is another size; the image was written to `actual`
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: PathBuf,
+    },
+    /// the image differs; it was written to `actual` and the diff image to `diff`
+    Mismatched {
+        diff_count: usize,
+        diff_ratio: f64,
+        actual: PathBuf,
+        diff: PathBuf,
+    },
+}
+
+/// asserts that an image matches the baseline PNG at `baseline`, panicking with
+/// the diff count, ratio and the written file paths otherwise.
+/// baselines are updated instead when `PIXELMATCH_UPDATE=1` is set.
+///
+/// # Arguments
+///
+/// * `baseline` - a path of the baseline PNG.
+/// * `image` - a rgba 24bit image data slice.
+/// * `dimensions` - a size of image.
+/// * `options` - an option for pixelmatch.
+pub fn assert_matches(
+    baseline: &Path,
+    image: &[u8],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+) {
+    let update = std::env::var(UPDATE_ENV).map_or(false, |value| value == "1");
+    let outcome = check(baseline, image, dimensions, options, update)
+        .unwrap_or_else(|err| panic!("image snapshot `{}`: {err}", baseline.display()));
+    let hint = format!("run with {UPDATE_ENV}=1 to accept the new image");
+    match outcome {
+        Outcome::Matched | Outcome::Updated => {}
+        Outcome::Missing { actual } => panic!(
+            "image snapshot `{}` does not exist\n  actual: {}\n{hint}",
+            baseline.display(),
+            actual.display()
+        ),
+        Outcome::SizeMismatch { expected, actual } => panic!(
+            "image snapshot `{}` is {}x{}, but the image is {}x{}\n  actual: {}\n{hint}",
+            baseline.display(),
+            expected.0,
+            expected.1,
+            dimensions.0,
+            dimensions.1,
+            actual.display()
+        ),
+        Outcome::Mismatched {
+            diff_count,
+            diff_ratio,
+            actual,
+            diff,
+        } => panic!(
+            "image snapshot `{}` does not match: {diff_count} pixels differ ({:.3}%)\n  actual: {}\n  diff: {}\n{hint}",
+            baseline.display(),
+            diff_ratio * 100.0,
+            actual.display(),
+            diff.display()
+        ),
+    }
+}
+
+/// compares an image with the baseline PNG at `baseline`, writing `<name>.actual.png`
+/// and `<name>.diff.png` next to it on failure; or writes the baseline if `update` is set
+/// and it doesn't match. files left over from an earlier failure are removed on success.
+///
+/// pixels of `<name>.mask` next to the baseline, a mask serialized by `IgnoreMask::to_rle`,
+/// are ignored unless `options` brings its own mask.
+pub fn check(
+    baseline: &Path,
+    image: &[u8],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+    update: bool,
+) -> Result<Outcome, Error> {
+    let actual_path = baseline.with_extension("actual.png");
+    let diff_path = baseline.with_extension("diff.png");
+    if image.len() != dimensions.0 as usize * dimensions.1 as usize * 4 {
+        return Err(pixelmatch::PixelmatchError::InvalidFormatError.into());
+    }
+
+    let outcome = if !baseline.exists() {
+        if update {
+            write_png(baseline, image, dimensions)?;
+            Outcome::Updated
+        } else {
+            write_png(&actual_path, image, dimensions)?;
+            return Ok(Outcome::Missing {
+                actual: actual_path,
+            });
+        }
+    } else {
+        let (expected, expected_dimensions) = read_png(baseline)?;
+        // the hash check may skip the diff image, which is always written on failure
+        let options = Some(PixelmatchOption {
+            hash_check: None,
+            ..with_mask(baseline, options)?
+        });
+        let diff = if expected_dimensions == dimensions {
+            Some(pixelmatch(&expected, image, dimensions, options)?)
+        } else {
+            None
+        };
+        match diff {
+            Some(diff) if diff.diff_count == 0 => Outcome::Matched,
+            _ if update => {
+                write_png(baseline, image, dimensions)?;
+                Outcome::Updated
+            }
+            None => {
+                write_png(&actual_path, image, dimensions)?;
+                return Ok(Outcome::SizeMismatch {
+                    expected: expected_dimensions,
+                    actual: actual_path,
+                });
+            }
+            Some(diff) => {
+                write_png(&actual_path, image, dimensions)?;
+                write_png(&diff_path, &diff.diff_image, dimensions)?;
+                return Ok(Outcome::Mismatched {
+                    diff_count: diff.diff_count,
+                    diff_ratio: diff.diff_count as f64 / (image.len() / 4) as f64,
+                    actual: actual_path,
+                    diff: diff_path,
+                });
+            }
+        }
+    };
+
+    for stale in [actual_path, diff_path] {
+        if stale.exists() {
+            fs::remove_file(stale)?;
+        }
+    }
+    Ok(outcome)
+}
+
+/// fills in the ignore mask saved next to a baseline, if there is one and none is given
+fn with_mask(
+    baseline: &Path,
+    options: Option<PixelmatchOption>,
+) -> Result<PixelmatchOption, Error> {
+    let mut options = options.unwrap_or_default();
+    let path = baseline.with_extension("mask");
+    if options.ignore_mask.is_none() && path.exists() {
+        options.ignore_mask = Some(IgnoreMask::from_rle(&fs::read(path)?)?);
+    }
+    Ok(options)
+}
+
+fn read_png(path: &Path) -> Result<(Vec<u8>, (u32, u32)), Error> {
+    Ok(decode_png(BufReader::new(File::open(path)?))?)
+}
+
+fn write_png(path: &Path, image: &[u8], dimensions: (u32, u32)) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    encode_png(BufWriter::new(File::create(path)?), image, dimensions)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_write_actual_and_diff_until_updated() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-snapshot-{}", std::process::id()));
+        let baseline = dir.join("button.png");
+        let white = vec![255; 3 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+
+        let outcome = check(&baseline, &white, (3, 2), None, false).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Missing {
+                actual: dir.join("button.actual.png")
+            }
+        );
+        assert_eq!(
+            check(&baseline, &white, (3, 2), None, true).unwrap(),
+            Outcome::Updated
+        );
+        assert_eq!(
+            check(&baseline, &white, (3, 2), None, false).unwrap(),
+            Outcome::Matched
+        );
+        assert!(!dir.join("button.actual.png").exists());
+
+        let outcome = check(&baseline, &changed, (3, 2), None, false).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Mismatched {
+                diff_count: 1,
+                diff_ratio: 1.0 / 6.0,
+                actual: dir.join("button.actual.png"),
+                diff: dir.join("button.diff.png"),
+            }
+        );
+        assert_eq!(
+            read_png(&dir.join("button.actual.png")).unwrap(),
+            (changed.clone(), (3, 2))
+        );
+
+        assert_eq!(
+            check(&baseline, &changed, (3, 2), None, true).unwrap(),
+            Outcome::Updated
+        );
+        assert!(!dir.join("button.diff.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_ignore_pixels_of_the_mask_next_to_the_baseline() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-mask-{}", std::process::id()));
+        let baseline = dir.join("clock.png");
+        let white = vec![255; 3 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        check(&baseline, &white, (3, 2), None, true).unwrap();
+        assert!(matches!(
+            check(&baseline, &changed, (3, 2), None, false).unwrap(),
+            Outcome::Mismatched { .. }
+        ));
+
+        let mask_option = pixelmatch::mask::MaskOption { dilation: 0 };
+        let mask = IgnoreMask::learn(&[&white, &changed], (3, 2), Some(mask_option), None).unwrap();
+        fs::write(dir.join("clock.mask"), mask.to_rle()).unwrap();
+        assert_eq!(
+            check(&baseline, &changed, (3, 2), None, false).unwrap(),
+            Outcome::Matched
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
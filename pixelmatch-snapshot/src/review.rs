@@ -0,0 +1,247 @@
+use super::{read_png, with_mask, Error};
+use pixelmatch::{pixelmatch, PixelmatchOption};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// a failed snapshot waiting for review
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pending {
+    /// the baseline the actual image would replace; it may not exist yet
+    pub baseline: PathBuf,
+    /// the image written by the failed assertion
+    pub actual: PathBuf,
+    /// the diff image written by the failed assertion, if any
+    pub diff: Option<PathBuf>,
+    /// the comparison with the baseline; none if it's missing or another size
+    pub stats: Option<DiffStats>,
+}
+
+/// a summary of a pending comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    /// a size of the images
+    pub dimensions: (u32, u32),
+    /// difference count against the baseline
+    pub diff_count: usize,
+    /// difference count divided by the number of pixels
+    pub diff_ratio: f64,
+}
+
+/// lists the `.actual.png` files under `dir` and its subdirectories, sorted by path,
+/// with their comparison against the baselines they belong to.
+///
+/// # Arguments
+///
+/// * `dir` - a snapshot directory.
+/// * `options` - an option for pixelmatch, used for the stats.
+pub fn pending(dir: &Path, options: Option<PixelmatchOption>) -> Result<Vec<Pending>, Error> {
+    let mut actuals = vec![];
+    collect(dir, &mut actuals)?;
+    actuals.sort();
+
+    let mut result = vec![];
+    for actual in actuals {
+        let name = actual.file_name().unwrap().to_string_lossy();
+        let stem = &name[..name.len() - ".actual.png".len()];
+        let baseline = actual.with_file_name(format!("{stem}.png"));
+        let diff = Some(actual.with_file_name(format!("{stem}.diff.png"))).filter(|d| d.exists());
+        let stats = if baseline.exists() {
+            let (expected, expected_dimensions) = read_png(&baseline)?;
+            let (image, dimensions) = read_png(&actual)?;
+            if expected_dimensions == dimensions {
+                // stats need a real count, so the hash check never skips the scan
+                let options = PixelmatchOption {
+                    hash_check: None,
+                    ..with_mask(&baseline, options.clone())?
+                };
+                let output = pixelmatch(&expected, &image, dimensions, Some(options))?;
+                Some(DiffStats {
+                    dimensions,
+                    diff_count: output.diff_count,
+                    diff_ratio: output.diff_count as f64 / (image.len() / 4).max(1) as f64,
+                })
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        result.push(Pending {
+            baseline,
+            actual,
+            diff,
+            stats,
+        });
+    }
+    Ok(result)
+}
+
+/// symlinks are skipped, so that a link back up the tree can't recurse forever
+/// and approving can't rename a file outside of it
+fn collect(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect(&path, out)?;
+        } else if file_type.is_file() && path.to_string_lossy().ends_with(".actual.png") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// accepts a pending image as the new baseline and appends a line to `log`.
+///
+/// the actual image is renamed over the baseline, which replaces it atomically since
+/// both are in the same directory; the diff image is removed.
+///
+/// # Arguments
+///
+/// * `pending` - a pending image from `pending`.
+/// * `approver` - who approved it, recorded in the log.
+/// * `log` - a path of the approval log.
+pub fn approve(pending: &Pending, approver: &str, log: &Path) -> Result<(), Error> {
+    fs::rename(&pending.actual, &pending.baseline)?;
+    if let Some(diff) = &pending.diff {
+        remove_if_exists(diff)?;
+    }
+    append_log(log, approver, "approved", &pending.baseline)
+}
+
+/// discards a pending image, keeping the baseline, and appends a line to `log`.
+pub fn reject(pending: &Pending, approver: &str, log: &Path) -> Result<(), Error> {
+    remove_if_exists(&pending.actual)?;
+    if let Some(diff) = &pending.diff {
+        remove_if_exists(diff)?;
+    }
+    append_log(log, approver, "rejected", &pending.baseline)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// one tab separated line per decision: unix time, approver, decision and baseline path
+fn append_log(log: &Path, approver: &str, decision: &str, baseline: &Path) -> Result<(), Error> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+    writeln!(
+        file,
+        "{time}\t{approver}\t{decision}\t{}",
+        baseline.display()
+    )?;
+    Ok(())
+}
+
+/// whether a path matches a glob pattern, to approve pending images selectively.
+/// `*` matches any characters except `/`, `**` also matches `/`, and `?` matches one character.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch_snapshot::review::glob_match;
+///
+/// assert!(glob_match("buttons/*.png", "buttons/primary.png"));
+/// assert!(!glob_match("*.png", "buttons/primary.png"));
+/// assert!(glob_match("**/primary.png", "buttons/primary.png"));
+/// ```
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let (pattern, path): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), path.chars().collect());
+    match_chars(&pattern, &path)
+}
+
+fn match_chars(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            let rest_without_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+            match_chars(rest_without_slash, path)
+                || (0..=path.len()).any(|i| match_chars(rest, &path[i..]))
+        }
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| match_chars(rest, &path[i..])),
+        ['?', rest @ ..] => matches!(path, [c, ..] if *c != '/') && match_chars(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && match_chars(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, Outcome};
+
+    #[test]
+    fn should_approve_and_reject_pending_images() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-review-{}", std::process::id()));
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        for name in ["a", "b"] {
+            let baseline = dir.join("buttons").join(format!("{name}.png"));
+            check(&baseline, &white, (2, 2), None, true).unwrap();
+            let outcome = check(&baseline, &changed, (2, 2), None, false).unwrap();
+            assert!(matches!(outcome, Outcome::Mismatched { .. }));
+        }
+
+        let log = dir.join("approvals.log");
+        let list = pending(&dir, None).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].stats.unwrap().diff_count, 1);
+        let relative = |p: &Pending| {
+            p.baseline
+                .strip_prefix(&dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        };
+        for p in &list {
+            if glob_match("buttons/a*", &relative(p)) {
+                approve(p, "alice", &log).unwrap();
+            } else {
+                reject(p, "alice", &log).unwrap();
+            }
+        }
+
+        assert!(pending(&dir, None).unwrap().is_empty());
+        let a = check(&dir.join("buttons/a.png"), &changed, (2, 2), None, false).unwrap();
+        let b = check(&dir.join("buttons/b.png"), &white, (2, 2), None, false).unwrap();
+        assert_eq!((a, b), (Outcome::Matched, Outcome::Matched));
+        let log = fs::read_to_string(&log).unwrap();
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.lines().next().unwrap().contains("\talice\tapproved\t"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_skip_symlinks() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-symlink-{}", std::process::id()));
+        let white = vec![255; 2 * 2 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        let baseline = dir.join("pages/home.png");
+        check(&baseline, &white, (2, 2), None, true).unwrap();
+        check(&baseline, &changed, (2, 2), None, false).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("pages/loop")).unwrap();
+        std::os::unix::fs::symlink(
+            dir.join("pages/home.actual.png"),
+            dir.join("pages/linked.actual.png"),
+        )
+        .unwrap();
+
+        let list = pending(&dir, None).unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].actual, dir.join("pages/home.actual.png"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,658 @@
+use super::review::{approve, pending, reject, Pending};
+use super::{read_png, Error};
+use pixelmatch::png_stream::encode_png;
+use pixelmatch::region::{find_regions, merge_regions};
+use pixelmatch::PixelmatchOption;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// regions closer than this are listed as one
+const REGION_MERGE_DISTANCE: u32 = 8;
+/// regions listed per pair at most
+const MAX_LISTED_REGIONS: usize = 5;
+
+/// renders an image for a truecolor terminal with upper half block characters,
+/// two pixels per character, scaled down to at most `columns` characters wide.
+/// semi-transparent pixels are blended over white.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch_snapshot::terminal::half_blocks;
+///
+/// let red_over_blue = vec![255, 0, 0, 255, 0, 0, 255, 255];
+/// assert_eq!(
+///     half_blocks(&red_over_blue, (1, 2), 80),
+///     "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n"
+/// );
+/// ```
+pub fn half_blocks(image: &[u8], dimensions: (u32, u32), columns: u32) -> String {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    if width == 0 || height == 0 || columns == 0 {
+        return String::new();
+    }
+    let out_width = width.min(columns as usize);
+    let out_height = (height * out_width + width - 1) / width;
+    let pixel = |x: usize, y: usize| {
+        // nearest neighbour, so that single different pixels don't fade away
+        let pos = ((y * height / out_height) * width + x * width / out_width) * 4;
+        let a = image[pos + 3] as u32;
+        let blend = |c: u8| ((c as u32 * a + 255 * (255 - a)) / 255) as u8;
+        (
+            blend(image[pos]),
+            blend(image[pos + 1]),
+            blend(image[pos + 2]),
+        )
+    };
+
+    let mut out = String::new();
+    for y in (0..out_height).step_by(2) {
+        for x in 0..out_width {
+            let (r, g, b) = pixel(x, y);
+            out += &format!("\x1b[38;2;{r};{g};{b}m");
+            if y + 1 < out_height {
+                let (r, g, b) = pixel(x, y + 1);
+                out += &format!("\x1b[48;2;{r};{g};{b}m▀");
+            } else {
+                out += "\x1b[49m▀";
+            }
+        }
+        out += "\x1b[0m\n";
+    }
+    out
+}
+
+/// how previews are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    /// truecolor upper half block characters; works in most terminals, also over SSH
+    HalfBlocks,
+    /// the kitty graphics protocol, also understood by WezTerm and Ghostty
+    Kitty,
+    /// sixel graphics, as in foot, mlterm or xterm with sixel support
+    Sixel,
+}
+
+impl Graphics {
+    /// guesses what the terminal supports from `KITTY_WINDOW_ID`, `TERM` and `TERM_PROGRAM`,
+    /// falling back to half blocks.
+    pub fn detect() -> Self {
+        Self::from_env(|name| std::env::var(name).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || ["WezTerm", "ghostty"].contains(&program.as_str())
+        {
+            Graphics::Kitty
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            Graphics::Sixel
+        } else {
+            Graphics::HalfBlocks
+        }
+    }
+}
+
+impl FromStr for Graphics {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "blocks" => Ok(Graphics::HalfBlocks),
+            "kitty" => Ok(Graphics::Kitty),
+            "sixel" => Ok(Graphics::Sixel),
+            _ => Err(format!(
+                "unknown graphics `{name}`; use blocks, kitty or sixel"
+            )),
+        }
+    }
+}
+
+/// how the images of a pair are previewed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preview {
+    /// how previews are drawn
+    pub graphics: Graphics,
+    /// the width of each preview in characters; half block previews are put side by side,
+    /// graphics are stacked and scaled down to `columns * CELL_WIDTH` pixels.
+    pub columns: u32,
+}
+
+/// a cell width in pixels assumed to size graphics previews
+pub const CELL_WIDTH: u32 = 8;
+
+/// draws an image with the kitty graphics protocol as a PNG, followed by a newline.
+/// the terminal is asked not to respond, so that nothing is echoed back to the input.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch_snapshot::terminal::kitty;
+///
+/// let out = kitty(&[255, 0, 0, 255], (1, 1)).unwrap();
+/// assert!(out.starts_with("\x1b_Ga=T,f=100,q=2,m=0;iVBORw0KGgo"));
+/// assert!(out.ends_with("\x1b\\\n"));
+/// ```
+pub fn kitty(image: &[u8], dimensions: (u32, u32)) -> Result<String, Error> {
+    let mut png = vec![];
+    encode_png(&mut png, image, dimensions)?;
+    let data = base64(&png);
+    let chunks = (data.len() + KITTY_CHUNK - 1) / KITTY_CHUNK;
+    let mut out = String::new();
+    for i in 0..chunks {
+        let chunk = &data[i * KITTY_CHUNK..((i + 1) * KITTY_CHUNK).min(data.len())];
+        let more = u8::from(i + 1 < chunks);
+        let control = if i == 0 {
+            format!("a=T,f=100,q=2,m={more}")
+        } else {
+            format!("m={more}")
+        };
+        out += &format!("\x1b_G{control};{chunk}\x1b\\");
+    }
+    out.push('\n');
+    Ok(out)
+}
+
+/// the largest payload of one kitty graphics escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// draws an image as sixel graphics with a 6x6x6 color cube, followed by a newline.
+/// semi-transparent pixels are blended over white.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch_snapshot::terminal::sixel;
+///
+/// let red_row = [255, 0, 0, 255].repeat(5);
+/// assert_eq!(
+///     sixel(&red_row, (5, 1)),
+///     "\x1bPq\"1;1;5;1#180;2;100;0;0#180!5@\x1b\\\n"
+/// );
+/// ```
+pub fn sixel(image: &[u8], dimensions: (u32, u32)) -> String {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    if width == 0 || height == 0 {
+        return String::new();
+    }
+    // each channel is rounded to one of 6 levels; a color is indexed by r * 36 + g * 6 + b
+    let level = |c: u8, a: u32| ((c as u32 * a + 255 * (255 - a)) / 255 * 5 + 127) / 255;
+    let colors: Vec<usize> = image
+        .chunks_exact(4)
+        .map(|p| {
+            let a = p[3] as u32;
+            (level(p[0], a) * 36 + level(p[1], a) * 6 + level(p[2], a)) as usize
+        })
+        .collect();
+
+    let mut out = format!("\x1bPq\"1;1;{width};{height}");
+    let mut used = [false; 216];
+    for &color in &colors {
+        used[color] = true;
+    }
+    for color in (0..216).filter(|&c| used[c]) {
+        let percent = |level: usize| level * 20;
+        out += &format!(
+            "#{color};2;{};{};{}",
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        );
+    }
+
+    // six rows per band; each color of a band is drawn in its own pass over the columns
+    for top in (0..height).step_by(6) {
+        if top > 0 {
+            out.push('-');
+        }
+        let rows = top..(top + 6).min(height);
+        let mut in_band = [false; 216];
+        for y in rows.clone() {
+            for &color in &colors[y * width..(y + 1) * width] {
+                in_band[color] = true;
+            }
+        }
+        for (pass, color) in (0..216).filter(|&c| in_band[c]).enumerate() {
+            if pass > 0 {
+                out.push('$');
+            }
+            out += &format!("#{color}");
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .filter(|&y| colors[y * width + x] == color)
+                    .fold(0, |bits, y| bits | 1 << (y - top));
+                let c = char::from(63 + bits as u8);
+                run = match run {
+                    Some((prev, count)) if prev == c => Some((c, count + 1)),
+                    Some(prev) => {
+                        push_run(&mut out, prev);
+                        Some((c, 1))
+                    }
+                    None => Some((c, 1)),
+                };
+            }
+            if let Some(run) = run {
+                push_run(&mut out, run);
+            }
+        }
+    }
+    out += "\x1b\\\n";
+    out
+}
+
+fn push_run(out: &mut String, (c, count): (char, usize)) {
+    if count > 3 {
+        *out += &format!("!{count}{c}");
+    } else {
+        for _ in 0..count {
+            out.push(c);
+        }
+    }
+}
+
+/// nearest neighbour scaling to at most `max_width` pixels wide
+fn scale_down(image: &[u8], dimensions: (u32, u32), max_width: u32) -> (Vec<u8>, (u32, u32)) {
+    let (width, height) = dimensions;
+    if width <= max_width || max_width == 0 {
+        return (image.to_vec(), dimensions);
+    }
+    let out_height = (height as u64 * max_width as u64 / width as u64).max(1) as u32;
+    let mut out = Vec::with_capacity(max_width as usize * out_height as usize * 4);
+    for y in 0..out_height as usize {
+        let src_y = y * height as usize / out_height as usize;
+        for x in 0..max_width as usize {
+            let pos = (src_y * width as usize + x * width as usize / max_width as usize) * 4;
+            out.extend_from_slice(&image[pos..pos + 4]);
+        }
+    }
+    (out, (max_width, out_height))
+}
+
+/// decisions taken in review_pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReviewSummary {
+    /// pairs whose actual image became the baseline
+    pub approved: usize,
+    /// pairs whose actual image was discarded
+    pub rejected: usize,
+    /// pairs left pending
+    pub skipped: usize,
+}
+
+/// a key pressed while reviewing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Approve,
+    Reject,
+    Next,
+    Previous,
+    Quit,
+    Other,
+}
+
+/// reads one key press; the end of input quits
+fn read_key<R: Read>(input: &mut R) -> io::Result<Key> {
+    let mut next = || -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        loop {
+            match input.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    };
+    Ok(match next()? {
+        // ctrl-c arrives as a byte in raw mode
+        None | Some(b'q' | 0x03) => Key::Quit,
+        Some(b'a') => Key::Approve,
+        Some(b'r') => Key::Reject,
+        Some(b'n' | b's' | b' ' | b'\r' | b'\n') => Key::Next,
+        Some(b'p') => Key::Previous,
+        // arrow keys
+        Some(0x1b) => match (next()?, next()?) {
+            (Some(b'['), Some(b'C')) => Key::Next,
+            (Some(b'['), Some(b'D')) => Key::Previous,
+            _ => Key::Other,
+        },
+        _ => Key::Other,
+    })
+}
+
+/// clears the screen and moves the cursor to the top left
+const CLEAR: &str = "\x1b[2J\x1b[H";
+
+/// steps through the pending images under `dir`, showing the baseline, actual and diff images
+/// with the diff stats and regions, and takes a decision for each pair with a single key:
+/// `a` to approve, `r` to reject, `n`, space or the right arrow to skip, `p` or the left arrow
+/// to go back and `q` to stop. each pair is drawn on a cleared screen.
+///
+/// keys are read byte by byte, so `input` should be a terminal in raw mode
+/// to take them without waiting for enter.
+///
+/// # Arguments
+///
+/// * `dir` - a snapshot directory.
+/// * `approver` - who decides, recorded in the log.
+/// * `log` - a path of the approval log.
+/// * `options` - an option for pixelmatch; its diff color is used to find the regions.
+/// * `preview` - how the images are drawn.
+/// * `input` - where keys are read from, e.g. stdin.
+/// * `output` - where the previews are written to, e.g. stdout.
+pub fn review_pending<R: Read, W: Write>(
+    dir: &Path,
+    approver: &str,
+    log: &Path,
+    options: Option<PixelmatchOption>,
+    preview: Preview,
+    mut input: R,
+    mut output: W,
+) -> Result<ReviewSummary, Error> {
+    let diff_color = options
+        .as_ref()
+        .map_or(pixelmatch::DEFAULT_DIFF_COLOR, |o| o.diff_color);
+    let mut summary = ReviewSummary::default();
+    let list = pending(dir, options)?;
+    let mut decided = vec![None; list.len()];
+    let (mut i, mut drawn) = (0, None);
+    while i < list.len() {
+        let p = &list[i];
+        if drawn != Some(i) {
+            write!(output, "{CLEAR}")?;
+            write!(
+                output,
+                "[{}/{}] {}",
+                i + 1,
+                list.len(),
+                p.baseline.display()
+            )?;
+            match decided[i] {
+                // the images are gone once decided
+                Some(decision) => writeln!(output, " ({decision})")?,
+                None => {
+                    writeln!(output)?;
+                    show(p, diff_color, preview, &mut output)?;
+                }
+            }
+            write!(
+                output,
+                "[a]pprove, [r]eject, [n]ext, [p]revious or [q]uit? "
+            )?;
+            output.flush()?;
+            drawn = Some(i);
+        }
+
+        match read_key(&mut input)? {
+            Key::Approve | Key::Reject if decided[i].is_some() => {}
+            Key::Approve => {
+                approve(p, approver, log)?;
+                decided[i] = Some("approved");
+                summary.approved += 1;
+                i += 1;
+            }
+            Key::Reject => {
+                reject(p, approver, log)?;
+                decided[i] = Some("rejected");
+                summary.rejected += 1;
+                i += 1;
+            }
+            Key::Next => i += 1,
+            Key::Previous => i = i.saturating_sub(1),
+            Key::Quit => break,
+            Key::Other => {}
+        }
+    }
+    writeln!(output)?;
+    summary.skipped = list.len() - summary.approved - summary.rejected;
+    Ok(summary)
+}
+
+fn show<W: Write>(
+    p: &Pending,
+    diff_color: pixelmatch::Rgba,
+    preview: Preview,
+    output: &mut W,
+) -> Result<(), Error> {
+    let mut images = vec![];
+    for (label, path) in [
+        ("baseline", Some(&p.baseline)),
+        ("actual", Some(&p.actual)),
+        ("diff", p.diff.as_ref()),
+    ] {
+        if let Some(path) = path.filter(|path| path.exists()) {
+            images.push((label, read_png(path)?));
+        }
+    }
+
+    if preview.graphics == Graphics::HalfBlocks {
+        let columns = preview.columns;
+        let previews: Vec<(String, usize)> = images
+            .iter()
+            .map(|(_, (image, dimensions))| {
+                let width = dimensions.0.min(columns) as usize;
+                (half_blocks(image, *dimensions, columns), width)
+            })
+            .collect();
+        // side by side, padding shorter previews with blank lines of their own width
+        let lines: Vec<(Vec<&str>, usize)> = previews
+            .iter()
+            .map(|(p, width)| (p.lines().collect(), *width))
+            .collect();
+        let rows = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        for row in 0..rows {
+            let cells: Vec<String> = lines
+                .iter()
+                .map(|(l, width)| {
+                    l.get(row)
+                        .map_or_else(|| " ".repeat(*width), |s| s.to_string())
+                })
+                .collect();
+            writeln!(output, "{}", cells.join(" "))?;
+        }
+    } else {
+        for (label, (image, dimensions)) in &images {
+            writeln!(output, "{label}")?;
+            let max_width = preview.columns.saturating_mul(CELL_WIDTH);
+            let (image, dimensions) = scale_down(image, *dimensions, max_width);
+            match preview.graphics {
+                Graphics::Kitty => write!(output, "{}", kitty(&image, dimensions)?)?,
+                _ => write!(output, "{}", sixel(&image, dimensions))?,
+            }
+        }
+    }
+
+    match p.stats {
+        Some(stats) => writeln!(
+            output,
+            "{}x{}, {} pixels differ ({:.3}%)",
+            stats.dimensions.0,
+            stats.dimensions.1,
+            stats.diff_count,
+            stats.diff_ratio * 100.0
+        )?,
+        None => writeln!(output, "no baseline of the same size")?,
+    }
+    if let Some(diff) = p.diff.as_ref().filter(|d| d.exists()) {
+        let (image, dimensions) = read_png(diff)?;
+        let regions = merge_regions(
+            &find_regions(&image, dimensions, diff_color)?,
+            REGION_MERGE_DISTANCE,
+        );
+        writeln!(output, "{} regions", regions.len())?;
+        for r in regions.iter().take(MAX_LISTED_REGIONS) {
+            writeln!(
+                output,
+                "  {}x{} at ({}, {}), {} pixels",
+                r.width, r.height, r.x, r.y, r.pixel_count
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::check;
+
+    #[test]
+    fn should_take_a_decision_per_pending_pair() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-terminal-{}", std::process::id()));
+        let white = vec![255; 4 * 4 * 4];
+        let mut changed = white.clone();
+        changed[..4].copy_from_slice(&[0, 0, 0, 255]);
+        for name in ["a", "b", "c"] {
+            let baseline = dir.join(format!("{name}.png"));
+            check(&baseline, &white, (4, 4), None, true).unwrap();
+            check(&baseline, &changed, (4, 4), None, false).unwrap();
+        }
+
+        let mut output = vec![];
+        let log = dir.join("approvals.log");
+        let preview = Preview {
+            graphics: Graphics::HalfBlocks,
+            columns: 4,
+        };
+        // skip a, an unknown key, back to a, reject a, skip b, approve c
+        let keys = &b"nx\x1b[Drna"[..];
+        let summary = review_pending(&dir, "bob", &log, None, preview, keys, &mut output).unwrap();
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                approved: 1,
+                rejected: 1,
+                skipped: 1
+            }
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[2/3]"));
+        assert!(output.contains("1 pixels differ (6.250%)"));
+        assert!(output.contains("1x1 at (0, 0), 1 pixels"));
+        // b is drawn twice, when first skipped and when skipped after going back
+        assert_eq!(output.matches("[2/3]").count(), 2);
+        assert_eq!(output.matches("[1/3]").count(), 2);
+        let left: Vec<_> = pending(&dir, None).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].baseline, dir.join("b.png"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_keep_previews_of_different_heights_aligned() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-preview-{}", std::process::id()));
+        let baseline = dir.join("a.png");
+        check(&baseline, &[255; 2 * 2 * 4], (2, 2), None, true).unwrap();
+        check(&baseline, &[0; 2 * 6 * 4], (2, 6), None, false).unwrap();
+        let p = Pending {
+            baseline,
+            actual: dir.join("a.actual.png"),
+            diff: None,
+            stats: None,
+        };
+
+        let mut output = vec![];
+        let preview = Preview {
+            graphics: Graphics::HalfBlocks,
+            columns: 8,
+        };
+        show(&p, pixelmatch::DEFAULT_DIFF_COLOR, preview, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        // the two pixel wide baseline ends after one line and is padded with two spaces
+        assert!(lines[1].starts_with("   \x1b["));
+        assert!(lines[2].starts_with("   \x1b["));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_detect_graphics_from_the_environment() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let detect = |vars| Graphics::from_env(env(vars));
+        assert_eq!(detect(&[("KITTY_WINDOW_ID", "1")]), Graphics::Kitty);
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), Graphics::Kitty);
+        assert_eq!(detect(&[("TERM_PROGRAM", "WezTerm")]), Graphics::Kitty);
+        assert_eq!(detect(&[("TERM", "foot")]), Graphics::Sixel);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), Graphics::HalfBlocks);
+        assert_eq!(detect(&[]), Graphics::HalfBlocks);
+        assert_eq!("sixel".parse(), Ok(Graphics::Sixel));
+        assert!("png".parse::<Graphics>().is_err());
+    }
+
+    #[test]
+    fn should_encode_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn should_split_kitty_images_into_chunks() {
+        // noise doesn't compress, so the PNG takes several chunks
+        let image: Vec<u8> = (0..64 * 64 * 4).map(|i| (i * 7919 % 251) as u8).collect();
+        let out = kitty(&image, (64, 64)).unwrap();
+        let sequences: Vec<&str> = out
+            .trim_end()
+            .split("\x1b\\")
+            .filter(|s| !s.is_empty())
+            .collect();
+        assert!(sequences.len() > 2);
+        assert!(sequences[0].starts_with("\x1b_Ga=T,f=100,q=2,m=1;"));
+        assert!(sequences[1].starts_with("\x1b_Gm=1;"));
+        assert!(sequences.last().unwrap().starts_with("\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn should_draw_sixel_bands_of_six_rows() {
+        // a 2x7 image; white everywhere but a black pixel at the top left and one in the second band
+        let mut image = vec![255; 2 * 7 * 4];
+        image[..4].copy_from_slice(&[0, 0, 0, 255]);
+        image[6 * 2 * 4 + 4..].copy_from_slice(&[0, 0, 0, 255]);
+        assert_eq!(
+            sixel(&image, (2, 7)),
+            "\x1bPq\"1;1;2;7#0;2;0;0;0#215;2;100;100;100#0@?$#215}~-#0?@$#215@?\x1b\\\n"
+        );
+    }
+
+    #[test]
+    fn should_scale_down_to_the_preview_width() {
+        let image: Vec<u8> = (0..4 * 2).flat_map(|i| [i as u8; 4]).collect();
+        let (scaled, dimensions) = scale_down(&image, (4, 2), 2);
+        assert_eq!(dimensions, (2, 1));
+        assert_eq!(scaled, [[0; 4], [2; 4]].concat());
+        assert_eq!(scale_down(&image, (4, 2), 8).1, (4, 2));
+    }
+}
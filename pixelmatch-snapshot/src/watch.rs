@@ -0,0 +1,228 @@
+use super::{check, read_png, Error, Outcome};
+use pixelmatch::PixelmatchOption;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// a screenshot with the outcome of its comparison, or why it couldn't be compared
+pub type Compared = (PathBuf, Result<Outcome, Error>);
+
+/// detects PNG files that were added or changed in a directory since the last look,
+/// to compare screenshots again as soon as they are written.
+///
+/// the directory is polled by modification time and size, so it works the same on every
+/// platform; `.actual.png` and `.diff.png` files written by failed assertions are left out.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pixelmatch_snapshot::watch::Watcher;
+/// use pixelmatch_snapshot::Outcome;
+/// use std::{thread, time::Duration};
+///
+/// let mut watcher = Watcher::new("screenshots");
+/// loop {
+///     for (path, outcome) in watcher.compare_changed("snapshots", None).unwrap() {
+///         match outcome {
+///             Ok(Outcome::Mismatched { diff_count, .. }) => {
+///                 println!("{}: {diff_count} pixels differ", path.display())
+///             }
+///             Ok(outcome) => println!("{}: {outcome:?}", path.display()),
+///             Err(err) => println!("{}: {err}", path.display()),
+///         }
+///     }
+///     thread::sleep(Duration::from_millis(200));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    dir: PathBuf,
+    seen: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl Watcher {
+    /// starts watching `dir` and its subdirectories; every existing file counts as changed
+    /// on the first call to `changed`.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// PNG files added or modified since the last call, sorted by path.
+    pub fn changed(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut current = HashMap::new();
+        scan(&self.dir, &mut current)?;
+        let mut changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, stamp)| self.seen.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+        self.seen = current;
+        Ok(changed)
+    }
+
+    /// compares each screenshot added or modified since the last call with the baseline
+    /// at the same relative path under `baselines`, like `check` without updating;
+    /// returns the screenshots with their outcomes, sorted by path.
+    ///
+    /// a screenshot that fails, e.g. because it is still being written, gets its error
+    /// instead of an outcome without holding up the others, and counts as changed again
+    /// on the next call.
+    pub fn compare_changed(
+        &mut self,
+        baselines: impl AsRef<Path>,
+        options: Option<PixelmatchOption>,
+    ) -> Result<Vec<Compared>, Error> {
+        let mut outcomes = vec![];
+        for path in self.changed()? {
+            let relative = path.strip_prefix(&self.dir).unwrap_or(&path);
+            let baseline = baselines.as_ref().join(relative);
+            let outcome = read_png(&path).and_then(|(image, dimensions)| {
+                check(&baseline, &image, dimensions, options.clone(), false)
+            });
+            if outcome.is_err() {
+                self.seen.remove(&path);
+            }
+            outcomes.push((path, outcome));
+        }
+        Ok(outcomes)
+    }
+}
+
+fn scan(dir: &Path, out: &mut HashMap<PathBuf, (SystemTime, u64)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // a file or directory removed since the directory was read is just left out
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if metadata.is_dir() {
+            match scan(&path, out) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                result => result?,
+            }
+            continue;
+        }
+        let name = path.to_string_lossy();
+        if name.ends_with(".png") && !name.ends_with(".actual.png") && !name.ends_with(".diff.png")
+        {
+            out.insert(path, (metadata.modified()?, metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_report_added_and_modified_screenshots() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("pages")).unwrap();
+        fs::write(dir.join("pages/home.png"), b"1").unwrap();
+        fs::write(dir.join("pages/home.actual.png"), b"1").unwrap();
+        fs::write(dir.join("notes.txt"), b"1").unwrap();
+
+        let mut watcher = Watcher::new(&dir);
+        assert_eq!(watcher.changed().unwrap(), vec![dir.join("pages/home.png")]);
+        assert!(watcher.changed().unwrap().is_empty());
+
+        fs::write(dir.join("pages/home.png"), b"22").unwrap();
+        fs::write(dir.join("about.png"), b"1").unwrap();
+        assert_eq!(
+            watcher.changed().unwrap(),
+            vec![dir.join("about.png"), dir.join("pages/home.png")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_compare_changed_screenshots_with_their_baselines() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-watch-cmp-{}", std::process::id()));
+        let (screenshots, baselines) = (dir.join("screenshots"), dir.join("snapshots"));
+        let white = vec![255; 4 * 4 * 4];
+        for name in ["home", "about"] {
+            let baseline = baselines.join(format!("pages/{name}.png"));
+            check(&baseline, &white, (4, 4), None, true).unwrap();
+        }
+        let home = screenshots.join("pages/home.png");
+        crate::write_png(&home, &white, (4, 4)).unwrap();
+
+        let mut watcher = Watcher::new(&screenshots);
+        let outcomes = watcher.compare_changed(&baselines, None).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, home);
+        assert_eq!(outcomes[0].1.as_ref().unwrap(), &Outcome::Matched);
+        assert!(watcher
+            .compare_changed(&baselines, None)
+            .unwrap()
+            .is_empty());
+
+        let mut changed = white;
+        changed[..8].copy_from_slice(&[0, 0, 0, 255, 0, 0, 0, 255]);
+        let about = screenshots.join("pages/about.png");
+        crate::write_png(&about, &changed, (4, 4)).unwrap();
+        let outcomes = watcher.compare_changed(&baselines, None).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0, about);
+        assert!(matches!(
+            outcomes[0].1,
+            Ok(Outcome::Mismatched { diff_count: 2, .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_keep_comparing_past_a_broken_screenshot() {
+        let dir = std::env::temp_dir().join(format!("pixelmatch-watch-bad-{}", std::process::id()));
+        let (screenshots, baselines) = (dir.join("screenshots"), dir.join("snapshots"));
+        let white = vec![255; 2 * 2 * 4];
+        for name in ["a", "b", "c"] {
+            check(
+                &baselines.join(format!("{name}.png")),
+                &white,
+                (2, 2),
+                None,
+                true,
+            )
+            .unwrap();
+            crate::write_png(&screenshots.join(format!("{name}.png")), &white, (2, 2)).unwrap();
+        }
+        // b is only half written
+        fs::write(screenshots.join("b.png"), b"\x89PNG").unwrap();
+
+        let mut watcher = Watcher::new(&screenshots);
+        let outcomes = watcher.compare_changed(&baselines, None).unwrap();
+        let paths: Vec<_> = outcomes.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            paths,
+            ["a", "b", "c"].map(|name| screenshots.join(format!("{name}.png")))
+        );
+        assert!(matches!(outcomes[0].1, Ok(Outcome::Matched)));
+        assert!(outcomes[1].1.is_err());
+        assert!(matches!(outcomes[2].1, Ok(Outcome::Matched)));
+
+        // only the broken one is compared again, until it can be read
+        let outcomes = watcher.compare_changed(&baselines, None).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(outcomes[0].1.is_err());
+        crate::write_png(&screenshots.join("b.png"), &white, (2, 2)).unwrap();
+        let outcomes = watcher.compare_changed(&baselines, None).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(outcomes[0].1, Ok(Outcome::Matched)));
+        assert!(watcher
+            .compare_changed(&baselines, None)
+            .unwrap()
+            .is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,6 +6,7 @@ const createDefaultOptions = () => {
   return {
     includeAntiAlias: false,
     threshold: 0.1,
+    comparisonMode: "yiq",
     diffColor: [255, 119, 119, 255],
     antiAliasedColor: [243, 156, 18, 255],
   };
@@ -46,7 +47,8 @@ const pixelmatch = (img1, img2, w, h, opts) => {
     antiAliasedColor[0],
     antiAliasedColor[1],
     antiAliasedColor[2],
-    antiAliasedColor[3]
+    antiAliasedColor[3],
+    (opts.comparisonMode ?? defaultOptions.comparisonMode) === "luminance"
   );
   if (countOrError < 0) {
     if (countOrError === -1) {
@@ -56,6 +56,7 @@ function passArray8ToWasm0(arg, malloc) {
  * @param {number} anti_aliased_color_g
  * @param {number} anti_aliased_color_b
  * @param {number} anti_aliased_color_a
+ * @param {boolean} only_brightness
  * @returns {number}
  */
 function inner(
@@ -73,7 +74,8 @@ function inner(
   anti_aliased_color_r,
   anti_aliased_color_g,
   anti_aliased_color_b,
-  anti_aliased_color_a
+  anti_aliased_color_a,
+  only_brightness
 ) {
   try {
     const ptr0 = passArray8ToWasm0(img1, wasm.__wbindgen_malloc);
@@ -100,7 +102,8 @@ function inner(
       anti_aliased_color_r,
       anti_aliased_color_g,
       anti_aliased_color_b,
-      anti_aliased_color_a
+      anti_aliased_color_a,
+      only_brightness
     );
     return ret;
   } finally {
@@ -134,7 +137,8 @@ export const pixelmatch = (img1, img2, w, h, opts) => {
     antiAliasedColor[0],
     antiAliasedColor[1],
     antiAliasedColor[2],
-    antiAliasedColor[3]
+    antiAliasedColor[3],
+    (opts.comparisonMode ?? defaultOptions.comparisonMode) === "luminance"
   );
 
   if (countOrError < 0) {
@@ -5,6 +5,7 @@ use wasm_bindgen::prelude::*;
 struct PixelmatchOption {
     pub include_anti_alias: bool,
     pub threshold: f32,
+    pub only_brightness: bool,
     pub diff_color: Rgba,
     pub anti_aliased_color: Rgba,
 }
@@ -31,6 +32,7 @@ pub fn pixelmatch(
     anti_aliased_color_g: u8,
     anti_aliased_color_b: u8,
     anti_aliased_color_a: u8,
+    only_brightness: bool,
 ) -> isize {
     if img1.len() != img2.len() {
         return IMAGE_LENGTH_ERROR;
@@ -42,6 +44,7 @@ pub fn pixelmatch(
     let options = PixelmatchOption {
         include_anti_alias,
         threshold,
+        only_brightness,
         diff_color: Rgba(diff_color_r, diff_color_g, diff_color_b, diff_color_a),
         anti_aliased_color: Rgba(
             anti_aliased_color_r,
@@ -53,15 +56,32 @@ pub fn pixelmatch(
 
     // maximum acceptable square distance between two colors;
     // 35215 is the maximum possible value for the YIQ difference metric
+    // and 255 is the maximum possible brightness difference
     let threshold = options.threshold;
-    let max_delta = 35215.0 * threshold * threshold;
+    let max_delta = if options.only_brightness {
+        (255.0 * threshold) * (255.0 * threshold)
+    } else {
+        35215.0 * threshold * threshold
+    };
     let mut diff_count = 0;
 
+    let stride = (width * 4) as usize;
     for y in 0..height {
+        // identical rows are similar everywhere; skip the per-pixel color distance
+        let row = y as usize * stride..(y as usize + 1) * stride;
+        if matches!((img1.get(row.clone()), img2.get(row)), (Some(row1), Some(row2)) if row1 == row2)
+        {
+            for x in 0..width {
+                let pos = ((y * width + x) * 4) as usize;
+                let y = blend(gray_pixel(img1, pos), 0.1);
+                draw_pixel(out, pos, &Rgba(y as u8, y as u8, y as u8, 255));
+            }
+            continue;
+        }
         for x in 0..width {
             let pos = ((y * width + x) * 4) as usize;
-            // squared YUV distance between colors at this pixel position
-            let delta = color_delta(img1, img2, pos, pos, false);
+            // squared YUV (or brightness) distance between colors at this pixel position
+            let delta = squared_delta(img1, img2, pos, options.only_brightness);
             if f32::abs(delta) > max_delta {
                 // check it's a real rendering difference or just anti-aliasing
                 if !options.include_anti_alias
@@ -104,6 +124,15 @@ fn gray_pixel(img: &[u8], pos: usize) -> u8 {
     }
 }
 
+fn squared_delta(img1: &[u8], img2: &[u8], pos: usize, only_brightness: bool) -> f32 {
+    if only_brightness {
+        let y = color_delta(img1, img2, pos, pos, true);
+        y * y
+    } else {
+        color_delta(img1, img2, pos, pos, false)
+    }
+}
+
 // calculate color difference according to the paper "Measuring perceived color difference
 // using YIQ NTSC transmission color space in mobile applications" by Y. Kotsarenko and F. Ramos
 fn color_delta(img1: &[u8], img2: &[u8], pos1: usize, pos2: usize, only_brightness: bool) -> f32 {
@@ -289,3 +318,73 @@ fn has_many_siblings(img: &[u8], x1: usize, y1: usize, width: u32, height: u32)
     }
     false
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn should_match_core_luminance_mode_on_hue_change() {
+        // pure red and a gray of the same brightness, then a white to black change
+        let img1: [u8; 16] = [
+            255, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255,
+        ];
+        let img2: [u8; 16] = [76, 76, 76, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255];
+        let mut out: [u8; 16] = [0; 16];
+
+        let result = pixelmatch(
+            &img1, &img2, &mut out, 2, 2, false, 0.1, 255, 119, 119, 255, 243, 156, 18, 255, true,
+        );
+        let core = ::pixelmatch::pixelmatch(
+            &img1,
+            &img2,
+            (2, 2),
+            Some(::pixelmatch::PixelmatchOption {
+                comparison_mode: ::pixelmatch::ComparisonMode::Luminance,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(result, 1);
+        assert_eq!(result as usize, core.diff_count);
+        assert_eq!(out[..], core.diff_image[..]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn should_match_core_diff_image_when_rows_are_skipped() {
+        // rows 0, 2 and 3 are identical and skipped, semi-transparent pixels included;
+        // row 1 differs in one pixel
+        let (w, h) = (4, 4);
+        let mut img1 = [0u8; 64];
+        for (i, pixel) in img1.chunks_mut(4).enumerate() {
+            pixel.copy_from_slice(&[
+                (i * 37) as u8,
+                (i * 11) as u8,
+                (i * 5) as u8,
+                (255 - i * 9) as u8,
+            ]);
+        }
+        let mut img2 = img1;
+        img2[(w + 2) * 4..(w + 3) * 4].copy_from_slice(&[0, 0, 0, 255]);
+        let mut out = [0u8; 64];
+
+        let result = pixelmatch(
+            &img1, &img2, &mut out, w as u32, h as u32, true, 0.1, 255, 119, 119, 255, 243, 156,
+            18, 255, false,
+        );
+        let core = ::pixelmatch::pixelmatch(
+            &img1,
+            &img2,
+            (w as u32, h as u32),
+            Some(::pixelmatch::PixelmatchOption {
+                include_anti_alias: true,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        assert_eq!(result, 1);
+        assert_eq!(result as usize, core.diff_count);
+        assert_eq!(out[..], core.diff_image[..]);
+    }
+}
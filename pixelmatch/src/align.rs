@@ -0,0 +1,257 @@
+use super::{Compositor, PixelmatchError, PixelmatchOption};
+use core::cmp;
+
+/// search radius at the coarsest pyramid level
+const COARSE_RADIUS: i32 = 8;
+/// the pyramid is not reduced below this size
+const MIN_LEVEL_SIZE: usize = 16;
+
+/// estimates the global translation of img2 relative to img1, so that the content of img2
+/// at `(x + dx, y + dy)` corresponds to img1 at `(x, y)`.
+///
+/// the offset is searched by a coarse-to-fine search over grayscale pyramids, minimizing
+/// the mean absolute brightness difference of the overlapping area;
+/// offsets whose overlap is smaller than half of the image in either direction are not considered.
+/// the images are composited with the alpha modes, backdrop and linear-light setting of `options`,
+/// as `pixelmatch` compares them.
+///
+/// # Arguments
+///
+/// * `img1` - a base rgba 24bit image data slice.
+/// * `img2` - a target rgba 24bit image data slice.
+/// * `dimensions` - a size of image.
+/// * `max_offset` - the largest offset to consider in each direction.
+/// * `options` - options the images are compared with.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::align::*;
+///
+/// let img1: Vec<u8> = (0..32 * 32).flat_map(|i| [(i * 37 % 251) as u8, 0, 0, 255]).collect();
+/// // img2 is img1 scrolled up by 3 rows
+/// let mut img2 = img1[3 * 32 * 4..].to_vec();
+/// img2.extend_from_slice(&img1[..3 * 32 * 4]);
+/// assert_eq!(estimate_offset(&img1, &img2, (32, 32), 4, None), Ok((0, -3)));
+/// ```
+pub fn estimate_offset(
+    img1: &[u8],
+    img2: &[u8],
+    dimensions: (u32, u32),
+    max_offset: u32,
+    options: Option<PixelmatchOption>,
+) -> Result<(i32, i32), PixelmatchError> {
+    let len = dimensions.0 as usize * dimensions.1 as usize * 4;
+    if img1.len() != len || img2.len() != len {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+    let options = options.unwrap_or_default();
+    Ok(estimate(
+        (
+            img1,
+            &Compositor::new(&options, options.img1_alpha_mode, dimensions.0),
+        ),
+        (
+            img2,
+            &Compositor::new(&options, options.img2_alpha_mode, dimensions.0),
+        ),
+        dimensions,
+        max_offset,
+    ))
+}
+
+/// `estimate_offset` with the compositors of both images given;
+/// the images must hold `dimensions` pixels.
+pub(crate) fn estimate(
+    (img1, compositor1): (&[u8], &Compositor),
+    (img2, compositor2): (&[u8], &Compositor),
+    dimensions: (u32, u32),
+    max_offset: u32,
+) -> (i32, i32) {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    if width == 0 || height == 0 {
+        return (0, 0);
+    }
+    // never look further than half of the image so the overlap stays meaningful
+    let max_offset = cmp::min(max_offset as usize, cmp::min(width, height) / 2) as i32;
+
+    let mut pyramid1 = vec![Plane::gray(img1, compositor1, width, height)];
+    let mut pyramid2 = vec![Plane::gray(img2, compositor2, width, height)];
+    while (max_offset >> (pyramid1.len() - 1)) > COARSE_RADIUS {
+        let (next1, next2) = match (
+            pyramid1.last().unwrap().half(),
+            pyramid2.last().unwrap().half(),
+        ) {
+            (Some(next1), Some(next2)) => (next1, next2),
+            _ => break,
+        };
+        pyramid1.push(next1);
+        pyramid2.push(next2);
+    }
+
+    let levels = pyramid1.len();
+    // an exhaustive search at the coarsest level, then refine by one pixel at each finer level
+    let mut radius = (max_offset + (1 << (levels - 1)) - 1) >> (levels - 1);
+    let mut best = (0, 0);
+    for level in (0..levels).rev() {
+        let limit = max_offset >> level;
+        let center = best;
+        let mut best_score = f32::INFINITY;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let offset = (center.0 + dx, center.1 + dy);
+                if offset.0.abs() > limit || offset.1.abs() > limit {
+                    continue;
+                }
+                let score = pyramid1[level].mean_abs_diff(&pyramid2[level], offset);
+                // prefer the smaller offset on ties
+                if score < best_score || (score == best_score && norm(offset) < norm(best)) {
+                    best_score = score;
+                    best = offset;
+                }
+            }
+        }
+        if level > 0 {
+            best = (best.0 * 2, best.1 * 2);
+            radius = 1;
+        }
+    }
+    best
+}
+
+fn norm((dx, dy): (i32, i32)) -> i32 {
+    dx.abs() + dy.abs()
+}
+
+/// a grayscale image
+struct Plane {
+    data: Vec<f32>,
+    width: usize,
+    height: usize,
+}
+
+impl Plane {
+    fn gray(img: &[u8], compositor: &Compositor, width: usize, height: usize) -> Self {
+        Self {
+            data: (0..width * height)
+                .map(|i| compositor.gray(img, i * 4) as f32)
+                .collect(),
+            width,
+            height,
+        }
+    }
+
+    /// halves the size by averaging 2x2 blocks; none if it would become too small
+    fn half(&self) -> Option<Self> {
+        let (width, height) = (self.width / 2, self.height / 2);
+        if width < MIN_LEVEL_SIZE || height < MIN_LEVEL_SIZE {
+            return None;
+        }
+        let at = |x: usize, y: usize| self.data[y * self.width + x];
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let sum = at(2 * x, 2 * y)
+                    + at(2 * x + 1, 2 * y)
+                    + at(2 * x, 2 * y + 1)
+                    + at(2 * x + 1, 2 * y + 1);
+                data.push(sum / 4.0);
+            }
+        }
+        Some(Self {
+            data,
+            width,
+            height,
+        })
+    }
+
+    /// mean absolute difference between this plane and `other` moved by `offset`, over the overlap
+    fn mean_abs_diff(&self, other: &Plane, (dx, dy): (i32, i32)) -> f32 {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let (x0, x1) = (cmp::max(0, -dx), cmp::min(width, width - dx));
+        let (y0, y1) = (cmp::max(0, -dy), cmp::min(height, height - dy));
+        if x0 >= x1 || y0 >= y1 {
+            return f32::INFINITY;
+        }
+        let mut sum = 0.0;
+        for y in y0..y1 {
+            let row1 = &self.data[y as usize * self.width..];
+            let row2 = &other.data[(y + dy) as usize * self.width..];
+            for x in x0..x1 {
+                sum += (row1[x as usize] - row2[(x + dx) as usize]).abs();
+            }
+        }
+        sum / ((x1 - x0) * (y1 - y0)) as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::AlphaMode;
+
+    #[test]
+    fn should_find_offset_through_the_pyramid() {
+        let (w, h) = (200usize, 150usize);
+        // smooth blobs with some texture, so every level of the pyramid has something to match
+        let scene = |x: i32, y: i32| {
+            let v = ((x as f32 / 9.0).sin() * (y as f32 / 7.0).cos() * 100.0 + 128.0) as u8;
+            [v, v.wrapping_mul(3), 255 - v, 255]
+        };
+        let render = |dx: i32, dy: i32| {
+            (0..(w * h) as i32)
+                .flat_map(|i| scene(i % w as i32 + dx, i / w as i32 + dy))
+                .collect::<Vec<u8>>()
+        };
+        let img1 = render(0, 0);
+        let img2 = render(-23, 17);
+        assert_eq!(
+            estimate_offset(&img1, &img2, (w as u32, h as u32), 40, None),
+            Ok((23, -17))
+        );
+        assert_eq!(
+            estimate_offset(&img1, &img1, (w as u32, h as u32), 40, None),
+            Ok((0, 0))
+        );
+    }
+
+    #[test]
+    fn should_reject_images_shorter_than_dimensions() {
+        let img = vec![255; 16 * 16 * 4];
+        assert_eq!(
+            estimate_offset(&img, &img[4..], (16, 16), 4, None),
+            Err(PixelmatchError::ImageLengthError)
+        );
+        assert_eq!(
+            estimate_offset(&img[4..], &img[4..], (16, 16), 4, None),
+            Err(PixelmatchError::ImageLengthError)
+        );
+    }
+
+    #[test]
+    fn should_estimate_on_composited_colors() {
+        let (w, h) = (32usize, 32usize);
+        // the pattern is in the color channels of transparent pixels, so it is gone once
+        // composited over the backdrop and only shows when alpha is ignored
+        let render = |dy: usize| {
+            (0..w * h)
+                .flat_map(|i| [((i / w + dy) * 37 % 251) as u8, 0, 0, 0])
+                .collect::<Vec<u8>>()
+        };
+        let (img1, img2) = (render(0), render(3));
+        let dimensions = (w as u32, h as u32);
+        assert_eq!(
+            estimate_offset(&img1, &img2, dimensions, 4, None),
+            Ok((0, 0))
+        );
+        let options = PixelmatchOption {
+            img1_alpha_mode: AlphaMode::Ignore,
+            img2_alpha_mode: AlphaMode::Ignore,
+            ..PixelmatchOption::default()
+        };
+        assert_eq!(
+            estimate_offset(&img1, &img2, dimensions, 4, Some(options)),
+            Ok((0, -3))
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use super::region::{draw_outline, find_regions};
+use super::{PixelmatchError, Rgba, DEFAULT_DIFF_COLOR};
+use png::{BitDepth, ColorType, Encoder};
+
+/// an error of animate
+#[derive(Debug)]
+pub enum AnimationError {
+    /// the images or the diff image are not the size of `dimensions`
+    Pixelmatch(PixelmatchError),
+    /// the frames could not be encoded
+    Encoding(png::EncodingError),
+}
+
+impl std::fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AnimationError::Pixelmatch(err) => err.fmt(f),
+            AnimationError::Encoding(err) => write!(f, "png encoding error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+impl From<PixelmatchError> for AnimationError {
+    fn from(err: PixelmatchError) -> Self {
+        AnimationError::Pixelmatch(err)
+    }
+}
+
+impl From<png::EncodingError> for AnimationError {
+    fn from(err: png::EncodingError) -> Self {
+        AnimationError::Encoding(err)
+    }
+}
+
+/// a kind of animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimationKind {
+    /// alternates between img1 and img2
+    #[default]
+    Blink,
+    /// cross-fades from img1 to img2 and back in `steps` steps
+    OnionSkin { steps: u32 },
+}
+
+/// an option for animate
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationOption {
+    /// a kind of animation
+    pub kind: AnimationKind,
+    /// how long each frame is shown in milliseconds
+    pub frame_delay_ms: u16,
+    /// color of different pixels in the diff image; used to locate the diff regions
+    pub diff_color: Rgba,
+    /// color of the outlines drawn around diff regions
+    pub outline_color: Rgba,
+    /// thickness of the outlines in pixels; 0 disables outlines
+    pub outline_thickness: u32,
+}
+
+impl Default for AnimationOption {
+    fn default() -> Self {
+        Self {
+            kind: AnimationKind::Blink,
+            frame_delay_ms: 500,
+            diff_color: DEFAULT_DIFF_COLOR,
+            outline_color: (255, 0, 255, 255),
+            outline_thickness: 1,
+        }
+    }
+}
+
+/// encodes an endlessly looping APNG that flips between img1 and img2 with the diff regions
+/// outlined, so that small offsets become visible at a glance.
+///
+/// # Arguments
+///
+/// * `img1` - a base rgba 24bit image data slice.
+/// * `img2` - a target rgba 24bit image data slice.
+/// * `diff_image` - a difference image returned by `pixelmatch` for img1 and img2.
+/// * `dimensions` - a size of image.
+/// * `options` - an option for animate.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::*;
+/// use pixelmatch::animation::*;
+///
+/// let img1 = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let result = pixelmatch(&img1, &img2, (2, 2), None).unwrap();
+/// let apng = animate(&img1, &img2, &result.diff_image, (2, 2), None).unwrap();
+/// assert_eq!(&apng[1..4], b"PNG");
+/// ```
+pub fn animate(
+    img1: &[u8],
+    img2: &[u8],
+    diff_image: &[u8],
+    dimensions: (u32, u32),
+    options: Option<AnimationOption>,
+) -> Result<Vec<u8>, AnimationError> {
+    let len = dimensions.0 as usize * dimensions.1 as usize * 4;
+    if img1.len() != len || img2.len() != len || diff_image.len() != len {
+        return Err(PixelmatchError::ImageLengthError.into());
+    }
+
+    let options = options.unwrap_or_default();
+    let mut frames = match options.kind {
+        AnimationKind::Blink => vec![img1.to_vec(), img2.to_vec()],
+        AnimationKind::OnionSkin { steps } => {
+            let steps = steps.max(1);
+            // there and back again; the last frame leads back into the first
+            (0..=steps)
+                .chain((1..steps).rev())
+                .map(|step| cross_fade(img1, img2, step as f32 / steps as f32))
+                .collect()
+        }
+    };
+
+    if options.outline_thickness > 0 {
+        let regions = find_regions(diff_image, dimensions, options.diff_color)?;
+        for frame in frames.iter_mut() {
+            for region in &regions {
+                draw_outline(
+                    frame,
+                    dimensions,
+                    region,
+                    options.outline_color,
+                    options.outline_thickness,
+                );
+            }
+        }
+    }
+
+    Ok(encode(&frames, dimensions, options.frame_delay_ms)?)
+}
+
+/// encodes rgba frames of the same size into an endlessly looping APNG
+fn encode(
+    frames: &[Vec<u8>],
+    dimensions: (u32, u32),
+    delay_ms: u16,
+) -> Result<Vec<u8>, png::EncodingError> {
+    let mut out = vec![];
+    let mut encoder = Encoder::new(&mut out, dimensions.0, dimensions.1);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    // played 0 times means looping forever
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(delay_ms, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame)?;
+    }
+    writer.finish()?;
+    Ok(out)
+}
+
+fn cross_fade(img1: &[u8], img2: &[u8], t: f32) -> Vec<u8> {
+    img1.iter()
+        .zip(img2)
+        .map(|(&a, &b)| (a as f32 + (b as f32 - a as f32) * t).round() as u8)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_encode_one_frame_per_step() {
+        let img1 = vec![0; 4 * 4 * 4];
+        let img2 = vec![255; 4 * 4 * 4];
+        let options = AnimationOption {
+            kind: AnimationKind::OnionSkin { steps: 4 },
+            ..AnimationOption::default()
+        };
+        let apng = animate(&img1, &img2, &img1, (4, 4), Some(options)).unwrap();
+        let frames = apng.windows(4).filter(|w| w == b"fcTL").count();
+        assert_eq!(frames, 8);
+    }
+
+    #[test]
+    fn should_blink_with_outlined_regions() {
+        let (w, h) = (5, 5);
+        let img1 = vec![255; w * h * 4];
+        let mut img2 = img1.clone();
+        let center = (2 * w + 2) * 4;
+        img2[center..center + 4].copy_from_slice(&[0, 0, 0, 255]);
+        let mut diff = vec![0; w * h * 4];
+        diff[center..center + 4].copy_from_slice(&[255, 0, 0, 255]);
+        let options = AnimationOption {
+            diff_color: (255, 0, 0, 255),
+            ..AnimationOption::default()
+        };
+        let apng = animate(&img1, &img2, &diff, (w as u32, h as u32), Some(options)).unwrap();
+        assert_eq!(apng.windows(4).filter(|w| w == b"fcTL").count(), 2);
+
+        let mut reader = png::Decoder::new(&apng[..]).read_info().unwrap();
+        let mut frame = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut frame).unwrap();
+        let pixel = |x: usize, y: usize| &frame[(y * w + x) * 4..(y * w + x) * 4 + 4];
+        // a ring of one pixel around the changed pixel, which itself is left alone
+        for (x, y) in [
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (1, 2),
+            (3, 2),
+            (1, 3),
+            (2, 3),
+            (3, 3),
+        ] {
+            assert_eq!(pixel(x, y), [255, 0, 255, 255]);
+        }
+        assert_eq!(pixel(2, 2), [255, 255, 255, 255]);
+        assert_eq!(pixel(0, 0), [255, 255, 255, 255]);
+    }
+}
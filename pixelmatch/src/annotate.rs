@@ -0,0 +1,262 @@
+use super::region::{draw_outline, find_regions, merge_regions, Region};
+use super::{PixelmatchError, Rgba, DEFAULT_DIFF_COLOR};
+use core::cmp;
+
+/// an option for annotate
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationOption {
+    /// color of different pixels in the diff image; used to locate the diff regions
+    pub diff_color: Rgba,
+    /// regions at most this many pixels apart are drawn as one rectangle
+    pub merge_distance: u32,
+    /// color of the rectangles and label backgrounds
+    pub color: Rgba,
+    /// thickness of the rectangles in pixels
+    pub thickness: u32,
+    /// whether to label each rectangle with its index and pixel count, like `#1 42px`
+    pub label: bool,
+    /// color of the label text
+    pub label_color: Rgba,
+    /// integer scale of the 5x7 label font
+    pub label_scale: u32,
+}
+
+impl Default for AnnotationOption {
+    fn default() -> Self {
+        Self {
+            diff_color: DEFAULT_DIFF_COLOR,
+            merge_distance: 8,
+            color: (255, 0, 255, 255),
+            thickness: 2,
+            label: true,
+            label_color: (255, 255, 255, 255),
+            label_scale: 1,
+        }
+    }
+}
+
+/// an output of annotate
+#[derive(Debug)]
+pub struct Annotation {
+    /// a rgba image data vec with the regions drawn on it.
+    pub image: Vec<u8>,
+    /// the annotated regions, in label order.
+    pub regions: Vec<Region>,
+}
+
+/// draws a rectangle around each cluster of differing pixels found in `diff_image`
+/// onto `img`, which may be the diff image itself or one of the compared images.
+///
+/// # Arguments
+///
+/// * `img` - a rgba 24bit image data slice to draw on.
+/// * `diff_image` - a difference image returned by `pixelmatch`.
+/// * `dimensions` - a size of image.
+/// * `options` - an option for annotate.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::*;
+/// use pixelmatch::annotate::*;
+///
+/// let img1 = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let result = pixelmatch(&img1, &img2, (2, 2), None).unwrap();
+/// let annotation = annotate(&img2, &result.diff_image, (2, 2), None).unwrap();
+/// assert_eq!(annotation.regions.len(), 1);
+/// ```
+pub fn annotate(
+    img: &[u8],
+    diff_image: &[u8],
+    dimensions: (u32, u32),
+    options: Option<AnnotationOption>,
+) -> Result<Annotation, PixelmatchError> {
+    let len = dimensions.0 as usize * dimensions.1 as usize * 4;
+    if img.len() != len || diff_image.len() != len {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+
+    let options = options.unwrap_or_default();
+    let regions = merge_regions(
+        &find_regions(diff_image, dimensions, options.diff_color)?,
+        options.merge_distance,
+    );
+
+    let mut image = img.to_vec();
+    for region in &regions {
+        draw_outline(
+            &mut image,
+            dimensions,
+            region,
+            options.color,
+            options.thickness,
+        );
+    }
+    if options.label {
+        for (i, region) in regions.iter().enumerate() {
+            let text = format!("#{} {}px", i + 1, region.pixel_count);
+            draw_label(&mut image, dimensions, region, &text, &options);
+        }
+    }
+
+    Ok(Annotation { image, regions })
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// rows of a 5x7 glyph, most significant of the 5 bits on the left
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        '#' => [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a],
+        'p' => [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10],
+        'x' => [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11],
+        _ => [0; 7],
+    }
+}
+
+/// draws `text` on a filled box just above the region, or below it if there is no room above.
+fn draw_label(
+    img: &mut [u8],
+    dimensions: (u32, u32),
+    region: &Region,
+    text: &str,
+    options: &AnnotationOption,
+) {
+    let scale = cmp::max(options.label_scale, 1);
+    let padding = scale;
+    let advance = (GLYPH_WIDTH + 1).saturating_mul(scale);
+    let box_width = (text.chars().count() as u32)
+        .saturating_mul(advance)
+        .saturating_add(padding.saturating_mul(2) - scale);
+    let box_height = GLYPH_HEIGHT
+        .saturating_mul(scale)
+        .saturating_add(padding.saturating_mul(2));
+
+    let outer_top = region.y.saturating_sub(options.thickness);
+    let top = if outer_top >= box_height {
+        outer_top - box_height
+    } else {
+        region
+            .y
+            .saturating_add(region.height)
+            .saturating_add(options.thickness)
+    };
+    // keep the label inside the image when there is no room below either
+    let (width, height) = dimensions;
+    let top = cmp::min(top, height.saturating_sub(box_height));
+    let left = cmp::min(
+        region.x.saturating_sub(options.thickness),
+        width.saturating_sub(box_width),
+    );
+
+    fill_rect(
+        img,
+        dimensions,
+        (left, top),
+        (box_width, box_height),
+        options.color,
+    );
+    for (i, c) in text.chars().enumerate() {
+        let origin = (
+            left.saturating_add(padding)
+                .saturating_add((i as u32).saturating_mul(advance)),
+            top.saturating_add(padding),
+        );
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) != 0 {
+                    let x = origin.0.saturating_add(col.saturating_mul(scale));
+                    let y = origin.1.saturating_add((row as u32).saturating_mul(scale));
+                    fill_rect(img, dimensions, (x, y), (scale, scale), options.label_color);
+                }
+            }
+        }
+    }
+}
+
+fn fill_rect(
+    img: &mut [u8],
+    dimensions: (u32, u32),
+    at: (u32, u32),
+    size: (u32, u32),
+    color: Rgba,
+) {
+    let (width, height) = dimensions;
+    for y in at.1..cmp::min(at.1.saturating_add(size.1), height) {
+        for x in at.0..cmp::min(at.0.saturating_add(size.0), width) {
+            let pos = ((y as usize) * width as usize + x as usize) * 4;
+            img[pos] = color.0;
+            img[pos + 1] = color.1;
+            img[pos + 2] = color.2;
+            img[pos + 3] = color.3;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_outline_and_label_regions() {
+        let (w, h) = (40, 40);
+        let img = vec![0; w * h * 4];
+        let mut diff = img.clone();
+        let pos = (20 * w + 20) * 4;
+        diff[pos..pos + 4].copy_from_slice(&[255, 119, 119, 255]);
+
+        let annotation = annotate(&img, &diff, (w as u32, h as u32), None).unwrap();
+        assert_eq!(annotation.regions.len(), 1);
+        let pixel = |x: usize, y: usize| &annotation.image[(y * w + x) * 4..(y * w + x) * 4 + 4];
+        // the rectangle surrounds the pixel without covering it
+        assert_eq!(pixel(18, 18), [255, 0, 255, 255]);
+        assert_eq!(pixel(20, 20), [0, 0, 0, 0]);
+        // the label is drawn above the rectangle
+        assert!((0..18).any(|y| (18..40).any(|x| pixel(x, y) == [255, 255, 255, 255])));
+    }
+
+    #[test]
+    fn should_keep_labels_of_huge_outlines_inside_the_image() {
+        let (w, h) = (40, 40);
+        let img = vec![0; w * h * 4];
+        let mut diff = img.clone();
+        let pos = (38 * w + 20) * 4;
+        diff[pos..pos + 4].copy_from_slice(&[255, 119, 119, 255]);
+
+        let options = AnnotationOption {
+            thickness: u32::MAX,
+            ..AnnotationOption::default()
+        };
+        let annotation = annotate(&img, &diff, (w as u32, h as u32), Some(options)).unwrap();
+        assert_eq!(annotation.regions.len(), 1);
+
+        // no room above or below the region in a short image; the label is moved up into it
+        let h = 10;
+        let mut diff = vec![0; w * h * 4];
+        for x in 4..12 {
+            diff[(4 * w + x) * 4..][..4].copy_from_slice(&[255, 119, 119, 255]);
+        }
+        let img = vec![0; w * h * 4];
+        let annotation = annotate(&img, &diff, (w as u32, h as u32), None).unwrap();
+        // the outline covers rows 2 to 6, so row 1 can only be the label box
+        assert!((0..w).any(|x| annotation.image[(w + x) * 4..][..4] == [255, 0, 255, 255]));
+
+        let options = AnnotationOption {
+            label_scale: u32::MAX,
+            ..AnnotationOption::default()
+        };
+        annotate(&img, &diff, (w as u32, h as u32), Some(options)).unwrap();
+    }
+}
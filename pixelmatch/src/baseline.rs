@@ -0,0 +1,222 @@
+use super::{
+    classify, pixelmatch, PixelClass, PixelmatchError, PixelmatchOption, PixelmatchOutput,
+    DEFAULT_DIFF_COLOR,
+};
+
+/// an output of best_match
+#[derive(Debug)]
+pub struct BestMatch {
+    /// index of the baseline with the fewest different pixels; the first one on ties
+    pub index: usize,
+    /// the comparison of the actual image with that baseline
+    pub output: PixelmatchOutput,
+}
+
+/// compares an actual image with each of several accepted baselines, e.g. renders from
+/// different platforms, and returns the one that matches best.
+///
+/// # Arguments
+///
+/// * `actual` - a rgba 24bit image data slice.
+/// * `baselines` - rgba 24bit image data slices of accepted renders; at least one.
+/// * `dimensions` - a size of the images.
+/// * `options` - an option for pixelmatch.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::baseline::*;
+///
+/// let baseline1 = vec![255, 255, 255, 255, 0, 0, 0, 255];
+/// let baseline2 = vec![0, 0, 0, 255, 0, 0, 0, 255];
+/// let actual = vec![0, 0, 0, 255, 0, 0, 0, 255];
+/// let result = best_match(&actual, &[&baseline1, &baseline2], (2, 1), None).unwrap();
+/// assert_eq!(result.index, 1);
+/// assert_eq!(result.output.diff_count, 0);
+/// ```
+pub fn best_match(
+    actual: &[u8],
+    baselines: &[&[u8]],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+) -> Result<BestMatch, PixelmatchError> {
+    Ok(compare_all(actual, baselines, dimensions, options)?
+        .into_iter()
+        .enumerate()
+        .min_by_key(|(_, output)| output.diff_count)
+        .map(|(index, output)| BestMatch { index, output })
+        .unwrap())
+}
+
+/// compares an actual image with several accepted baselines pixel by pixel:
+/// a pixel is only counted as different if it differs from every baseline.
+///
+/// each pixel of the diff image is taken from a comparison it passed, if any.
+/// `hash_check` and `offset` of the output are those of the best matching baseline;
+/// if that baseline matches as a whole, e.g. hash-identical, its output is returned as is.
+///
+/// # Arguments
+///
+/// * `actual` - a rgba 24bit image data slice.
+/// * `baselines` - rgba 24bit image data slices of accepted renders; at least one.
+/// * `dimensions` - a size of the images.
+/// * `options` - an option for pixelmatch.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::baseline::*;
+///
+/// // each baseline has a different glyph in one of the pixels
+/// let baseline1 = vec![255, 255, 255, 255, 0, 0, 0, 255];
+/// let baseline2 = vec![0, 0, 0, 255, 255, 255, 255, 255];
+/// let actual = vec![255, 255, 255, 255, 255, 255, 255, 255];
+/// let result = match_any(&actual, &[&baseline1, &baseline2], (2, 1), None).unwrap();
+/// assert_eq!(result.diff_count, 0);
+/// ```
+pub fn match_any(
+    actual: &[u8],
+    baselines: &[&[u8]],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+) -> Result<PixelmatchOutput, PixelmatchError> {
+    if baselines.is_empty() {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+    let diff_color = options
+        .as_ref()
+        .map_or(DEFAULT_DIFF_COLOR, |options| options.diff_color);
+    let mut compared = vec![];
+    for baseline in baselines {
+        let mut classes = vec![];
+        let output = classify(baseline, actual, dimensions, options.clone(), &mut classes)?;
+        compared.push((output, classes));
+    }
+    let best = (0..compared.len())
+        .min_by_key(|&i| compared[i].0.diff_count)
+        .unwrap();
+    // skipped scans have no classes to combine
+    let scanned: Vec<&(PixelmatchOutput, Vec<PixelClass>)> = compared
+        .iter()
+        .filter(|(_, classes)| !classes.is_empty())
+        .collect();
+    if compared[best].0.diff_count == 0 || scanned.is_empty() {
+        return Ok(compared.swap_remove(best).0);
+    }
+
+    let mut diff_count = 0;
+    let mut shifted_count = 0;
+    let mut diff_image = vec![0; actual.len()];
+    for (i, out) in diff_image.chunks_mut(4).enumerate() {
+        let class_of = |(_, classes): &&(PixelmatchOutput, Vec<PixelClass>)| classes[i];
+        // prefer a similar pixel to a shifted one
+        let chosen = scanned
+            .iter()
+            .find(|c| matches!(class_of(c), PixelClass::Similar | PixelClass::AntiAliased))
+            .or_else(|| scanned.iter().find(|c| class_of(c) == PixelClass::Shifted));
+        match chosen {
+            Some((output, classes)) => {
+                out.copy_from_slice(&output.diff_image[i * 4..i * 4 + 4]);
+                if classes[i] == PixelClass::Shifted {
+                    shifted_count += 1;
+                }
+            }
+            None => {
+                let (r, g, b, a) = diff_color;
+                out.copy_from_slice(&[r, g, b, a]);
+                diff_count += 1;
+            }
+        }
+    }
+
+    let best = compared.swap_remove(best).0;
+    Ok(PixelmatchOutput {
+        diff_count,
+        shifted_count,
+        diff_image,
+        ..best
+    })
+}
+
+fn compare_all(
+    actual: &[u8],
+    baselines: &[&[u8]],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+) -> Result<Vec<PixelmatchOutput>, PixelmatchError> {
+    if baselines.is_empty() {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+    baselines
+        .iter()
+        .map(|baseline| pixelmatch(baseline, actual, dimensions, options.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{HashCheck, HashVerdict};
+
+    #[test]
+    fn should_accept_pixels_matching_any_baseline() {
+        let gray = |v: u8| [v, v, v, 255];
+        // two distros render the same word with glyph differences in different places
+        let baseline1 = [gray(0), gray(0), gray(255), gray(255)].concat();
+        let baseline2 = [gray(0), gray(255), gray(0), gray(255)].concat();
+        let actual = [gray(0), gray(0), gray(0), gray(0)].concat();
+        let baselines = [&baseline1[..], &baseline2[..]];
+
+        let best = best_match(&actual, &baselines, (4, 1), None).unwrap();
+        assert_eq!((best.index, best.output.diff_count), (0, 2));
+
+        let any = match_any(&actual, &baselines, (4, 1), None).unwrap();
+        // only the last pixel differs from both
+        assert_eq!(any.diff_count, 1);
+        assert_eq!(any.diff_image[12..], [255, 119, 119, 255]);
+        assert_eq!(any.diff_image[4..8], best.output.diff_image[4..8]);
+
+        // a diff color that looks like a similar black pixel doesn't change the verdict
+        let options = PixelmatchOption {
+            diff_color: (229, 229, 229, 255),
+            ..PixelmatchOption::default()
+        };
+        let any = match_any(&actual, &baselines, (4, 1), Some(options)).unwrap();
+        assert_eq!(any.diff_count, 1);
+        assert_eq!(any.diff_image[..4], best.output.diff_image[..4]);
+    }
+
+    #[test]
+    fn should_agree_with_best_match_on_hash_identical_baseline() {
+        let (w, h) = (16, 16);
+        let actual = vec![255; w * h * 4];
+        // the left half is dark, so the hashes differ and the pixels are scanned
+        let scanned: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                if i % w < w / 2 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255; 4]
+                }
+            })
+            .collect();
+        let baselines = [&actual[..], &scanned[..]];
+        let options = PixelmatchOption {
+            hash_check: Some(HashCheck {
+                gross_distance: u32::MAX,
+                ..HashCheck::default()
+            }),
+            ..PixelmatchOption::default()
+        };
+
+        let best = best_match(&actual, &baselines, (16, 16), Some(options.clone())).unwrap();
+        let any = match_any(&actual, &baselines, (16, 16), Some(options)).unwrap();
+        assert_eq!(best.index, 0);
+        assert_eq!(any.diff_count, best.output.diff_count);
+        assert_eq!(any.hash_check, best.output.hash_check);
+        assert_eq!(
+            any.hash_check.map(|c| c.verdict),
+            Some(HashVerdict::Identical)
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use super::hash::{composited_hash, ImageHash};
+use super::{compare, Compositor, PixelmatchError, PixelmatchOption, PixelmatchOutput};
+
+/// a baseline image prepared for comparing many images with it, e.g. renders from
+/// several browsers or retries.
+///
+/// the blended YIQ colors, the sibling flags used by the anti-aliasing detection and
+/// the diff image background of the baseline are computed once, instead of on every call;
+/// the results are identical to `pixelmatch` with the baseline as img1.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::comparator::*;
+///
+/// let baseline = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let comparator = Comparator::new(&baseline, (2, 2), None).unwrap();
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// assert_eq!(comparator.compare(&img2).unwrap().diff_count, 1);
+/// assert_eq!(comparator.compare(&baseline).unwrap().diff_count, 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Comparator {
+    baseline: Vec<u8>,
+    dimensions: (u32, u32),
+    options: PixelmatchOption,
+    compositor: Compositor,
+    hash: Option<ImageHash>,
+}
+
+impl Comparator {
+    /// prepares a baseline.
+    ///
+    /// # Arguments
+    ///
+    /// * `baseline` - a base rgba 24bit image data slice, compared as img1.
+    /// * `dimensions` - a size of image.
+    /// * `options` - an option for pixelmatch, used for every comparison.
+    pub fn new(
+        baseline: &[u8],
+        dimensions: (u32, u32),
+        options: Option<PixelmatchOption>,
+    ) -> Result<Self, PixelmatchError> {
+        if baseline.len() != dimensions.0 as usize * dimensions.1 as usize * 4 {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+        let options = options.unwrap_or_default();
+        if let Some(mask) = &options.ignore_mask {
+            if mask.dimensions() != dimensions {
+                return Err(PixelmatchError::InvalidFormatError);
+            }
+        }
+        let mut compositor = Compositor::new(&options, options.img1_alpha_mode, dimensions.0);
+        compositor.prepare(baseline, dimensions);
+        Ok(Self {
+            baseline: baseline.to_vec(),
+            dimensions,
+            hash: options.hash_check.map(|check| {
+                composited_hash(
+                    baseline,
+                    dimensions,
+                    check.algorithm,
+                    &compositor,
+                    options.ignore_mask.as_ref(),
+                )
+            }),
+            options,
+            compositor,
+        })
+    }
+
+    /// compares an image with the baseline, like `pixelmatch(baseline, img2, dimensions, options)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `img2` - a target rgba 24bit image data slice of the same size as the baseline.
+    pub fn compare(&self, img2: &[u8]) -> Result<PixelmatchOutput, PixelmatchError> {
+        if img2.len() != self.baseline.len() {
+            return Err(PixelmatchError::ImageLengthError);
+        }
+        Ok(compare(
+            (&self.baseline, &self.compositor, self.hash),
+            img2,
+            self.dimensions,
+            &self.options,
+            None,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{pixelmatch, Backdrop};
+
+    #[test]
+    fn should_match_pixelmatch_for_each_image() {
+        let (w, h) = (9usize, 7usize);
+        let pattern = |seed: usize| {
+            (0..w * h)
+                .flat_map(|i| {
+                    let v = ((i * seed) % 11 * 23) as u8;
+                    [v, v / 3, 255 - v, if i % 4 == 0 { 100 } else { 255 }]
+                })
+                .collect::<Vec<u8>>()
+        };
+        let baseline = pattern(5);
+        for backdrop in [
+            Backdrop::default(),
+            Backdrop::WhiteAndBlack,
+            Backdrop::Checkerboard {
+                size: 2,
+                light: (255, 255, 255),
+                dark: (190, 190, 190),
+            },
+        ] {
+            for linear_light in [false, true] {
+                let options = PixelmatchOption {
+                    include_anti_alias: true,
+                    backdrop,
+                    linear_light,
+                    shift_radius: 2,
+                    ..PixelmatchOption::default()
+                };
+                let comparator =
+                    Comparator::new(&baseline, (w as u32, h as u32), Some(options.clone()))
+                        .unwrap();
+                for seed in [5, 7, 13] {
+                    let img2 = pattern(seed);
+                    let expected = pixelmatch(
+                        &baseline,
+                        &img2,
+                        (w as u32, h as u32),
+                        Some(options.clone()),
+                    )
+                    .unwrap();
+                    let result = comparator.compare(&img2).unwrap();
+                    assert_eq!(result.diff_count, expected.diff_count);
+                    assert_eq!(result.shifted_count, expected.shifted_count);
+                    assert_eq!(result.diff_image, expected.diff_image);
+                }
+            }
+        }
+    }
+}
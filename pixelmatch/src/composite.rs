@@ -0,0 +1,142 @@
+use super::{PixelmatchError, Rgba};
+
+/// how the panels of a composite image are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// baseline | diff | actual from left to right
+    Horizontal,
+    /// baseline, diff and actual from top to bottom
+    Vertical,
+    /// vertical for images wider than they are tall, horizontal otherwise
+    #[default]
+    Auto,
+}
+
+/// an option for composite
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositeOption {
+    /// how the panels are arranged
+    pub layout: Layout,
+    /// space between panels in pixels
+    pub gutter: u32,
+    /// color of the space between panels
+    pub gutter_color: Rgba,
+}
+
+impl Default for CompositeOption {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Auto,
+            gutter: 8,
+            gutter_color: (255, 255, 255, 255),
+        }
+    }
+}
+
+/// an output of composite
+#[derive(Debug)]
+pub struct CompositeImage {
+    /// a rgba image data vec.
+    pub image: Vec<u8>,
+    /// a size of image.
+    pub dimensions: (u32, u32),
+}
+
+/// renders the baseline, the diff and the actual image into a single rgba image,
+/// so one artifact is enough to review a change.
+///
+/// # Arguments
+///
+/// * `img1` - a base rgba 24bit image data slice.
+/// * `diff_image` - a difference image returned by `pixelmatch` for img1 and img2.
+/// * `img2` - a target rgba 24bit image data slice.
+/// * `dimensions` - a size of each image.
+/// * `options` - an option for composite.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::*;
+/// use pixelmatch::composite::*;
+///
+/// let img1 = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let result = pixelmatch(&img1, &img2, (2, 2), None).unwrap();
+/// let composite = composite(&img1, &result.diff_image, &img2, (2, 2), None).unwrap();
+/// assert_eq!(composite.dimensions, (22, 2));
+/// ```
+pub fn composite(
+    img1: &[u8],
+    diff_image: &[u8],
+    img2: &[u8],
+    dimensions: (u32, u32),
+    options: Option<CompositeOption>,
+) -> Result<CompositeImage, PixelmatchError> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let len = width * height * 4;
+    if img1.len() != len || diff_image.len() != len || img2.len() != len {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+
+    let options = options.unwrap_or_default();
+    let gutter = options.gutter as usize;
+    let vertical = match options.layout {
+        Layout::Horizontal => false,
+        Layout::Vertical => true,
+        Layout::Auto => width > height,
+    };
+    let (out_width, out_height) = if vertical {
+        (width, height * 3 + gutter * 2)
+    } else {
+        (width * 3 + gutter * 2, height)
+    };
+
+    let (r, g, b, a) = options.gutter_color;
+    let mut image = [r, g, b, a].repeat(out_width * out_height);
+    for (i, panel) in [img1, diff_image, img2].iter().enumerate() {
+        let (left, top) = if vertical {
+            (0, i * (height + gutter))
+        } else {
+            (i * (width + gutter), 0)
+        };
+        for y in 0..height {
+            let dst = ((top + y) * out_width + left) * 4;
+            image[dst..dst + width * 4].copy_from_slice(&panel[y * width * 4..(y + 1) * width * 4]);
+        }
+    }
+
+    Ok(CompositeImage {
+        image,
+        dimensions: (out_width as u32, out_height as u32),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_place_panels_side_by_side() {
+        let img1 = [1, 1, 1, 255];
+        let diff = [2, 2, 2, 255];
+        let img2 = [3, 3, 3, 255];
+        let options = CompositeOption {
+            layout: Layout::Horizontal,
+            gutter: 1,
+            gutter_color: (0, 0, 0, 0),
+        };
+        let result = composite(&img1, &diff, &img2, (1, 1), Some(options)).unwrap();
+        assert_eq!(result.dimensions, (5, 1));
+        assert_eq!(
+            result.image,
+            vec![1, 1, 1, 255, 0, 0, 0, 0, 2, 2, 2, 255, 0, 0, 0, 0, 3, 3, 3, 255]
+        );
+    }
+
+    #[test]
+    fn should_stack_wide_images() {
+        let img = [0; 2 * 4];
+        let result = composite(&img, &img, &img, (2, 1), None).unwrap();
+        assert_eq!(result.dimensions, (2, 19));
+    }
+}
@@ -0,0 +1,259 @@
+use super::mask::IgnoreMask;
+use super::{AlphaMode, Compositor, PixelmatchError, PixelmatchOption};
+use core::cmp;
+
+/// an algorithm used to compute a 64-bit perceptual hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// aHash; an 8x8 grayscale thumbnail thresholded at its mean brightness
+    Average,
+    /// dHash; the sign of horizontal brightness gradients of a 9x8 grayscale thumbnail
+    Difference,
+    /// pHash; the low-frequency DCT coefficients of a 32x32 grayscale thumbnail
+    /// thresholded at their median
+    Perceptual,
+}
+
+/// a 64-bit perceptual hash of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageHash(pub u64);
+
+impl ImageHash {
+    /// number of differing bits between two hashes (0 to 64); smaller is more similar
+    pub fn distance(&self, other: &ImageHash) -> u32 {
+        hamming_distance(*self, *other)
+    }
+}
+
+/// number of differing bits between two hashes (0 to 64).
+pub fn hamming_distance(a: ImageHash, b: ImageHash) -> u32 {
+    (a.0 ^ b.0).count_ones()
+}
+
+/// computes a perceptual hash of a rgba image with the given algorithm.
+///
+/// # Arguments
+///
+/// * `img` - a rgba 24bit image data slice of `dimensions`.
+/// * `dimensions` - a size of image.
+/// * `algorithm` - a hash algorithm.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::hash::*;
+///
+/// let img = vec![255; 16 * 16 * 4];
+/// let a = image_hash(&img, (16, 16), HashAlgorithm::Difference).unwrap();
+/// let b = image_hash(&img, (16, 16), HashAlgorithm::Difference).unwrap();
+/// assert_eq!(a.distance(&b), 0);
+/// ```
+pub fn image_hash(
+    img: &[u8],
+    dimensions: (u32, u32),
+    algorithm: HashAlgorithm,
+) -> Result<ImageHash, PixelmatchError> {
+    if img.len() != dimensions.0 as usize * dimensions.1 as usize * 4 {
+        return Err(PixelmatchError::InvalidFormatError);
+    }
+    let compositor = Compositor::new(
+        &PixelmatchOption::default(),
+        AlphaMode::Straight,
+        dimensions.0,
+    );
+    Ok(composited_hash(
+        img,
+        dimensions,
+        algorithm,
+        &compositor,
+        None,
+    ))
+}
+
+/// computes a perceptual hash of an image as the comparison sees it: composited by `compositor`
+/// and with the pixels of `mask` left out of the thumbnail.
+/// `img` must be sized `dimensions`.
+pub(crate) fn composited_hash(
+    img: &[u8],
+    dimensions: (u32, u32),
+    algorithm: HashAlgorithm,
+    compositor: &Compositor,
+    mask: Option<&IgnoreMask>,
+) -> ImageHash {
+    let thumbnail = |size| thumbnail(img, dimensions, size, compositor, mask);
+    match algorithm {
+        HashAlgorithm::Average => {
+            let thumb = thumbnail((8, 8));
+            let mean = thumb.iter().sum::<f32>() / thumb.len() as f32;
+            ImageHash(to_bits(thumb.iter().map(|&v| v > mean)))
+        }
+        HashAlgorithm::Difference => {
+            let thumb = thumbnail((9, 8));
+            let bits = (0..8).flat_map(|y| {
+                let row = &thumb[y * 9..(y + 1) * 9];
+                (0..8).map(move |x| row[x + 1] > row[x])
+            });
+            ImageHash(to_bits(bits))
+        }
+        HashAlgorithm::Perceptual => {
+            const SIZE: usize = 32;
+            let thumb = thumbnail((SIZE, SIZE));
+            let coefficients = dct_low_frequencies(&thumb, SIZE);
+            // the DC term only carries the average brightness, so leave it out of the median
+            let mut sorted = coefficients[1..].to_vec();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let median = (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0;
+            ImageHash(to_bits(coefficients.iter().map(|&c| c > median)))
+        }
+    }
+}
+
+/// computes an average hash (aHash) of a rgba image.
+pub fn ahash(img: &[u8], dimensions: (u32, u32)) -> Result<ImageHash, PixelmatchError> {
+    image_hash(img, dimensions, HashAlgorithm::Average)
+}
+
+/// computes a difference hash (dHash) of a rgba image.
+pub fn dhash(img: &[u8], dimensions: (u32, u32)) -> Result<ImageHash, PixelmatchError> {
+    image_hash(img, dimensions, HashAlgorithm::Difference)
+}
+
+/// computes a DCT based perceptual hash (pHash) of a rgba image.
+pub fn phash(img: &[u8], dimensions: (u32, u32)) -> Result<ImageHash, PixelmatchError> {
+    image_hash(img, dimensions, HashAlgorithm::Perceptual)
+}
+
+fn to_bits(bits: impl Iterator<Item = bool>) -> u64 {
+    bits.take(64)
+        .enumerate()
+        .fold(0, |acc, (i, bit)| if bit { acc | 1 << i } else { acc })
+}
+
+/// shrinks an image into a grayscale thumbnail by averaging the pixels that fall into each cell;
+/// ignored pixels are left out, and a cell with only ignored pixels is black.
+fn thumbnail(
+    img: &[u8],
+    dimensions: (u32, u32),
+    size: (usize, usize),
+    compositor: &Compositor,
+    mask: Option<&IgnoreMask>,
+) -> Vec<f32> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let mut thumb = vec![0.0; size.0 * size.1];
+    if width == 0 || height == 0 {
+        return thumb;
+    }
+    for ty in 0..size.1 {
+        let (y0, y1) = cell_range(ty, size.1, height);
+        for tx in 0..size.0 {
+            let (x0, x1) = cell_range(tx, size.0, width);
+            let (mut sum, mut count) = (0.0, 0);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    if mask.map_or(false, |mask| mask.is_ignored(x as u32, y as u32)) {
+                        continue;
+                    }
+                    sum += compositor.gray(img, (y * width + x) * 4) as f32;
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                thumb[ty * size.0 + tx] = sum / count as f32;
+            }
+        }
+    }
+    thumb
+}
+
+/// source pixel range covered by a thumbnail cell; at least one pixel even for tiny images.
+fn cell_range(cell: usize, cells: usize, len: usize) -> (usize, usize) {
+    let start = cmp::min(cell * len / cells, len - 1);
+    let end = cmp::min((cell + 1) * len / cells, len);
+    (start, end.max(start + 1))
+}
+
+/// top-left 8x8 coefficients of the 2D DCT-II of a square grayscale thumbnail.
+fn dct_low_frequencies(thumb: &[f32], size: usize) -> Vec<f32> {
+    const LOW: usize = 8;
+    let cosines: Vec<f32> = (0..LOW * size)
+        .map(|i| {
+            let (u, x) = (i / size, i % size);
+            ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / (2 * size) as f32).cos()
+        })
+        .collect();
+
+    // transform rows first, then the columns of the result
+    let mut rows = vec![0.0; size * LOW];
+    for y in 0..size {
+        for u in 0..LOW {
+            rows[y * LOW + u] = (0..size)
+                .map(|x| thumb[y * size + x] * cosines[u * size + x])
+                .sum();
+        }
+    }
+    let mut coefficients = vec![0.0; LOW * LOW];
+    for v in 0..LOW {
+        for u in 0..LOW {
+            coefficients[v * LOW + u] = (0..size)
+                .map(|y| rows[y * LOW + u] * cosines[v * size + y])
+                .sum();
+        }
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient(width: u32, height: u32, invert: bool) -> Vec<u8> {
+        let mut img = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let v = ((x * 7 + y * 3) % 256) as u8;
+                let v = if invert { 255 - v } else { v };
+                img.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn should_hash_same_images_equally() {
+        let img = gradient(40, 30, false);
+        for algorithm in [
+            HashAlgorithm::Average,
+            HashAlgorithm::Difference,
+            HashAlgorithm::Perceptual,
+        ] {
+            let a = image_hash(&img, (40, 30), algorithm).unwrap();
+            let b = image_hash(&img.clone(), (40, 30), algorithm).unwrap();
+            assert_eq!(a.distance(&b), 0);
+        }
+    }
+
+    #[test]
+    fn should_hash_inverted_images_far_apart() {
+        let img1 = gradient(40, 30, false);
+        let img2 = gradient(40, 30, true);
+        for algorithm in [
+            HashAlgorithm::Average,
+            HashAlgorithm::Difference,
+            HashAlgorithm::Perceptual,
+        ] {
+            let a = image_hash(&img1, (40, 30), algorithm).unwrap();
+            let b = image_hash(&img2, (40, 30), algorithm).unwrap();
+            assert!(a.distance(&b) > 32);
+        }
+    }
+
+    #[test]
+    fn should_reject_image_of_wrong_length() {
+        for hash in [ahash, dhash, phash] {
+            assert_eq!(
+                hash(&[0; 4], (16, 16)),
+                Err(PixelmatchError::InvalidFormatError)
+            );
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use super::{pixelmatch, PixelmatchError, PixelmatchOption};
+use core::cell::RefCell;
+use core::cmp;
+use std::collections::HashMap;
+
+/// an option for layout_diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LayoutOption {
+    /// whether to align columns as well, after the rows
+    pub columns: bool,
+}
+
+/// a band of consecutive rows or columns present in only one of the images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Band {
+    /// rows or columns of img1 that were removed from img2
+    Removed { start: u32, len: u32 },
+    /// rows or columns of img2 that were inserted, missing in img1
+    Inserted { start: u32, len: u32 },
+}
+
+/// an output of layout_diff
+#[derive(Debug)]
+pub struct LayoutDiff {
+    /// inserted and removed bands of rows
+    pub row_bands: Vec<Band>,
+    /// inserted and removed bands of columns; empty unless `columns` is set
+    pub column_bands: Vec<Band>,
+    /// matched rows as pairs of a row of img1 and a row of img2, from top to bottom
+    pub rows: Vec<(u32, u32)>,
+    /// matched columns as pairs of a column of img1 and a column of img2, from left to right
+    pub columns: Vec<(u32, u32)>,
+    /// difference count of the matched rows and columns
+    pub diff_count: usize,
+    /// a rgba difference image of the matched rows and columns, sized `dimensions`
+    pub diff_image: Vec<u8>,
+    /// a size of diff image; the number of matched columns and rows
+    pub dimensions: (u32, u32),
+}
+
+/// compares two images whose layout may have shifted, e.g. by a banner inserted at the top.
+///
+/// rows are aligned by a longest common subsequence of row hashes; rows in between that
+/// exist in both images are paired up and everything left over is reported as an inserted
+/// or removed band. only the matched rows (and columns, if enabled) are compared with `pixelmatch`.
+/// the images may differ in height, and in width when `columns` is set; rows and columns
+/// are then realigned over what is matched on the other axis until the alignment settles.
+///
+/// # Arguments
+///
+/// * `img1` - a base rgba 24bit image data slice.
+/// * `dimensions1` - a size of img1.
+/// * `img2` - a target rgba 24bit image data slice.
+/// * `dimensions2` - a size of img2.
+/// * `layout` - an option for layout_diff.
+/// * `options` - an option for pixelmatch, used on the matched rows.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::layout::*;
+///
+/// let row = |v: u8| vec![v, v, v, 255];
+/// let img1 = [row(10), row(20), row(30)].concat();
+/// let img2 = [row(0), row(10), row(20), row(30)].concat();
+/// let result = layout_diff(&img1, (1, 3), &img2, (1, 4), None, None).unwrap();
+/// assert_eq!(result.row_bands, vec![Band::Inserted { start: 0, len: 1 }]);
+/// assert_eq!(result.diff_count, 0);
+/// ```
+pub fn layout_diff(
+    img1: &[u8],
+    dimensions1: (u32, u32),
+    img2: &[u8],
+    dimensions2: (u32, u32),
+    layout: Option<LayoutOption>,
+    options: Option<PixelmatchOption>,
+) -> Result<LayoutDiff, PixelmatchError> {
+    let (width1, height1) = (dimensions1.0 as usize, dimensions1.1 as usize);
+    let (width2, height2) = (dimensions2.0 as usize, dimensions2.1 as usize);
+    if img1.len() != width1 * height1 * 4 || img2.len() != width2 * height2 * 4 {
+        return Err(PixelmatchError::InvalidFormatError);
+    }
+    let layout = layout.unwrap_or_default();
+    if !layout.columns && width1 != width2 {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+
+    // indexed by height rather than chunked, so that rows of a zero-width image still count
+    let rows1 = (0..height1)
+        .map(|y| &img1[y * width1 * 4..(y + 1) * width1 * 4])
+        .collect::<Vec<&[u8]>>();
+    let rows2 = (0..height2)
+        .map(|y| &img2[y * width2 * 4..(y + 1) * width2 * 4])
+        .collect::<Vec<&[u8]>>();
+    let (mut rows, mut row_bands) = if width1 == width2 {
+        let row_hashes = |rows: &[&[u8]]| {
+            rows.iter()
+                .map(|row| hash(row.iter().copied()))
+                .collect::<Vec<u64>>()
+        };
+        let (hashes1, hashes2) = (row_hashes(&rows1), row_hashes(&rows2));
+        align(height1, height2, |i, j| hashes1[i] == hashes2[j])
+    } else {
+        // whole rows can't be equal when columns were inserted or removed,
+        // so start from rows that are the same except for the extra columns.
+        // the LCS asks for the same pairs many times, so each pair of distinct rows
+        // is checked once
+        let (distinct1, distinct2) = (DistinctRows::new(&rows1), DistinctRows::new(&rows2));
+        let checked = RefCell::new(HashMap::new());
+        align(height1, height2, |i, j| {
+            let (id1, id2) = (distinct1.ids[i], distinct2.ids[j]);
+            *checked.borrow_mut().entry((id1, id2)).or_insert_with(|| {
+                let (row1, row2) = (&distinct1.rows[id1], &distinct2.rows[id2]);
+                if width1 < width2 {
+                    row1.is_subsequence(row2)
+                } else {
+                    row2.is_subsequence(row1)
+                }
+            })
+        })
+    };
+
+    let (columns, column_bands) = if layout.columns {
+        // only the matched rows, so that the columns of both images are the same length
+        let column_hashes = |rows: &[(usize, usize)], img: &[u8], width: usize, second: bool| {
+            (0..width)
+                .map(|x| {
+                    hash(rows.iter().flat_map(|&(y1, y2)| {
+                        let pos = ((if second { y2 } else { y1 }) * width + x) * 4;
+                        img[pos..pos + 4].iter().copied()
+                    }))
+                })
+                .collect::<Vec<u64>>()
+        };
+        let align_columns = |rows: &[(usize, usize)]| {
+            let hashes1 = column_hashes(rows, img1, width1, false);
+            let hashes2 = column_hashes(rows, img2, width2, true);
+            align(width1, width2, |i, j| hashes1[i] == hashes2[j])
+        };
+        // align each axis over what is matched on the other one until neither changes
+        let mut columns = align_columns(&rows);
+        for _ in 0..MAX_ALIGN_ROUNDS {
+            let row_hashes = |img: &[u8], width: usize, height: usize, second: bool| {
+                (0..height)
+                    .map(|y| {
+                        hash(columns.0.iter().flat_map(|&(x1, x2)| {
+                            let pos = (y * width + if second { x2 } else { x1 }) * 4;
+                            img[pos..pos + 4].iter().copied()
+                        }))
+                    })
+                    .collect::<Vec<u64>>()
+            };
+            let hashes1 = row_hashes(img1, width1, height1, false);
+            let hashes2 = row_hashes(img2, width2, height2, true);
+            let realigned = align(height1, height2, |i, j| hashes1[i] == hashes2[j]);
+            if realigned.0 == rows {
+                break;
+            }
+            (rows, row_bands) = realigned;
+            columns = align_columns(&rows);
+        }
+        columns
+    } else {
+        ((0..width1).map(|x| (x, x)).collect(), vec![])
+    };
+
+    let gather = |img: &[u8], width: usize, pick: fn(&(usize, usize)) -> usize| {
+        let mut out = Vec::with_capacity(rows.len() * columns.len() * 4);
+        for row in &rows {
+            for column in &columns {
+                let pos = (pick(row) * width + pick(column)) * 4;
+                out.extend_from_slice(&img[pos..pos + 4]);
+            }
+        }
+        out
+    };
+    let dimensions = (columns.len() as u32, rows.len() as u32);
+    let result = pixelmatch(
+        &gather(img1, width1, |pair| pair.0),
+        &gather(img2, width2, |pair| pair.1),
+        dimensions,
+        options,
+    )?;
+
+    let pairs = |pairs: Vec<(usize, usize)>| {
+        pairs
+            .into_iter()
+            .map(|(a, b)| (a as u32, b as u32))
+            .collect()
+    };
+    Ok(LayoutDiff {
+        row_bands,
+        column_bands,
+        rows: pairs(rows),
+        columns: pairs(columns),
+        diff_count: result.diff_count,
+        diff_image: result.diff_image,
+        dimensions,
+    })
+}
+
+/// how many times rows and columns are realigned over each other at most
+const MAX_ALIGN_ROUNDS: usize = 4;
+
+/// buckets of the pixel histogram of a row
+const HISTOGRAM_BUCKETS: usize = 32;
+
+/// the rows of an image numbered by their content, so that equal rows are checked once
+struct DistinctRows<'a> {
+    /// the number of the content of each row
+    ids: Vec<usize>,
+    /// each distinct row
+    rows: Vec<Row<'a>>,
+}
+
+impl<'a> DistinctRows<'a> {
+    fn new(rows: &[&'a [u8]]) -> Self {
+        let mut numbers = HashMap::new();
+        let mut distinct = vec![];
+        let ids = rows
+            .iter()
+            .map(|&row| {
+                *numbers.entry(row).or_insert_with(|| {
+                    distinct.push(Row::new(row));
+                    distinct.len() - 1
+                })
+            })
+            .collect();
+        Self {
+            ids,
+            rows: distinct,
+        }
+    }
+}
+
+/// a row with a histogram of its pixels
+struct Row<'a> {
+    pixels: &'a [u8],
+    histogram: [u32; HISTOGRAM_BUCKETS],
+}
+
+impl<'a> Row<'a> {
+    fn new(pixels: &'a [u8]) -> Self {
+        let mut histogram = [0; HISTOGRAM_BUCKETS];
+        for pixel in pixels.chunks(4) {
+            histogram[hash(pixel.iter().copied()) as usize % HISTOGRAM_BUCKETS] += 1;
+        }
+        Self { pixels, histogram }
+    }
+
+    /// whether the pixels of this row appear in `long` in the same order;
+    /// rows with more pixels of a kind than `long` are ruled out by their histograms first
+    fn is_subsequence(&self, long: &Row) -> bool {
+        if self
+            .histogram
+            .iter()
+            .zip(&long.histogram)
+            .any(|(a, b)| a > b)
+        {
+            return false;
+        }
+        let mut long = long.pixels.chunks(4);
+        self.pixels
+            .chunks(4)
+            .all(|pixel| long.by_ref().any(|other| other == pixel))
+    }
+}
+
+/// FNV-1a
+fn hash(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// aligns two sequences; returns the matched index pairs and the bands left over.
+/// equal items are matched by their longest common subsequence, and the items in between
+/// that exist on both sides are paired up in order, as modified rather than inserted or removed.
+fn align(
+    n: usize,
+    m: usize,
+    eq: impl Fn(usize, usize) -> bool,
+) -> (Vec<(usize, usize)>, Vec<Band>) {
+    let mut matched = vec![];
+    let mut bands = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (ci, cj) in lcs(n, m, eq).into_iter().chain(core::iter::once((n, m))) {
+        let (removed, inserted) = (ci - i, cj - j);
+        let modified = cmp::min(removed, inserted);
+        matched.extend((0..modified).map(|k| (i + k, j + k)));
+        if removed > modified {
+            bands.push(Band::Removed {
+                start: (i + modified) as u32,
+                len: (removed - modified) as u32,
+            });
+        }
+        if inserted > modified {
+            bands.push(Band::Inserted {
+                start: (j + modified) as u32,
+                len: (inserted - modified) as u32,
+            });
+        }
+        if ci < n {
+            matched.push((ci, cj));
+        }
+        (i, j) = (ci + 1, cj + 1);
+    }
+    (matched, bands)
+}
+
+/// index pairs of a longest common subsequence of two sequences of lengths `n` and `m`,
+/// whose items at `i` and `j` are equal if `eq(i, j)`.
+/// the common prefix and suffix are skipped first, and the part in between is solved
+/// with Hirschberg's algorithm, so memory stays linear in the length of the sequences.
+fn lcs(n: usize, m: usize, eq: impl Fn(usize, usize) -> bool) -> Vec<(usize, usize)> {
+    let prefix = (0..cmp::min(n, m)).take_while(|&k| eq(k, k)).count();
+    let suffix = (0..cmp::min(n, m) - prefix)
+        .take_while(|&k| eq(n - 1 - k, m - 1 - k))
+        .count();
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|k| (k, k)).collect();
+    hirschberg((prefix, n - suffix), (prefix, m - suffix), &eq, &mut pairs);
+    pairs.extend((0..suffix).map(|k| (n - suffix + k, m - suffix + k)));
+    pairs
+}
+
+/// appends the index pairs of a longest common subsequence of the ranges `a` and `b`
+/// to `pairs`, by splitting `a` in half and `b` where the halves' subsequences meet.
+fn hirschberg(
+    a: (usize, usize),
+    b: (usize, usize),
+    eq: &impl Fn(usize, usize) -> bool,
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if a.0 >= a.1 || b.0 >= b.1 {
+        return;
+    }
+    if a.1 - a.0 == 1 {
+        if let Some(j) = (b.0..b.1).find(|&j| eq(a.0, j)) {
+            pairs.push((a.0, j));
+        }
+        return;
+    }
+    let mid = (a.0 + a.1) / 2;
+    let forward = lcs_lengths((a.0..mid).map(|i| (b.0..b.1).map(move |j| (i, j))), eq);
+    let backward = lcs_lengths(
+        (mid..a.1)
+            .rev()
+            .map(|i| (b.0..b.1).rev().map(move |j| (i, j))),
+        eq,
+    );
+    let width = b.1 - b.0;
+    let split = (0..=width)
+        .max_by_key(|&k| (forward[k] + backward[width - k], cmp::Reverse(k)))
+        .unwrap();
+    hirschberg((a.0, mid), (b.0, b.0 + split), eq, pairs);
+    hirschberg((mid, a.1), (b.0 + split, b.1), eq, pairs);
+}
+
+/// the last row of the LCS length table over `rows` of index pairs, kept one row at a time;
+/// `lengths[k]` is the length for the first `k` items of each row.
+fn lcs_lengths<R: Iterator<Item = (usize, usize)>>(
+    rows: impl Iterator<Item = R>,
+    eq: &impl Fn(usize, usize) -> bool,
+) -> Vec<u32> {
+    let mut lengths: Vec<u32> = vec![];
+    for row in rows {
+        let mut previous = 0;
+        let mut current = vec![0];
+        for (k, (i, j)) in row.enumerate() {
+            let above = lengths.get(k + 1).copied().unwrap_or(0);
+            let length = if eq(i, j) {
+                previous + 1
+            } else {
+                cmp::max(above, current[k])
+            };
+            previous = above;
+            current.push(length);
+        }
+        lengths = current;
+    }
+    lengths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image(width: usize, rows: &[u8]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|&v| (0..width).flat_map(move |x| [v, x as u8 * 40, 0, 255]))
+            .collect()
+    }
+
+    #[test]
+    fn should_report_inserted_banner_and_modified_row() {
+        let img1 = image(3, &[10, 20, 30, 40, 50, 60]);
+        // a banner of two rows pushes the page down, and the last row falls off
+        let mut img2 = image(3, &[200, 210, 10, 20, 30, 40]);
+        // one row is modified in place
+        img2[3 * 3 * 4] = 255;
+
+        let result = layout_diff(&img1, (3, 6), &img2, (3, 6), None, None).unwrap();
+        assert_eq!(
+            result.row_bands,
+            vec![
+                Band::Inserted { start: 0, len: 2 },
+                Band::Removed { start: 4, len: 2 }
+            ]
+        );
+        assert_eq!(result.rows, vec![(0, 2), (1, 3), (2, 4), (3, 5)]);
+        assert_eq!(result.dimensions, (3, 4));
+        assert_eq!(result.diff_count, 1);
+    }
+
+    #[test]
+    fn should_align_columns_of_images_of_different_sizes() {
+        let img1 = image(3, &[10, 20]);
+        // an extra column on the left and an extra row at the bottom
+        let img2: Vec<u8> = image(3, &[10, 20, 30])
+            .chunks(3 * 4)
+            .flat_map(|row| [&[9, 9, 9, 255][..], row].concat())
+            .collect();
+
+        let layout = LayoutOption { columns: true };
+        let result = layout_diff(&img1, (3, 2), &img2, (4, 3), Some(layout), None).unwrap();
+        assert_eq!(result.row_bands, vec![Band::Inserted { start: 2, len: 1 }]);
+        assert_eq!(
+            result.column_bands,
+            vec![Band::Inserted { start: 0, len: 1 }]
+        );
+        assert_eq!(result.columns, vec![(0, 1), (1, 2), (2, 3)]);
+        assert_eq!(result.diff_count, 0);
+    }
+
+    #[test]
+    fn should_align_an_inserted_row_and_column_together() {
+        let img1 = image(3, &[10, 20, 30, 40]);
+        // a banner row at the top and an extra column in the middle
+        let img2: Vec<u8> = image(3, &[200, 10, 20, 30, 40])
+            .chunks(3 * 4)
+            .flat_map(|row| [&row[..4], &[9, 9, 9, 255][..], &row[4..]].concat())
+            .collect();
+
+        let layout = LayoutOption { columns: true };
+        let result = layout_diff(&img1, (3, 4), &img2, (4, 5), Some(layout), None).unwrap();
+        assert_eq!(result.row_bands, vec![Band::Inserted { start: 0, len: 1 }]);
+        assert_eq!(result.rows, vec![(0, 1), (1, 2), (2, 3), (3, 4)]);
+        assert_eq!(
+            result.column_bands,
+            vec![Band::Inserted { start: 1, len: 1 }]
+        );
+        assert_eq!(result.columns, vec![(0, 0), (1, 2), (2, 3)]);
+        assert_eq!(result.diff_count, 0);
+    }
+
+    #[test]
+    fn should_align_rows_of_tall_images_with_an_inserted_column() {
+        let (w, h) = (120usize, 400usize);
+        let pixel = |x: usize, y: usize| [(x * 7 + y) as u8, (y / 3) as u8, (x ^ y) as u8, 255];
+        let img1: Vec<u8> = (0..w * h).flat_map(|i| pixel(i % w, i / w)).collect();
+        // a column inserted in the middle, a banner row at the top and the last row gone,
+        // so that neither end of the rows matches and all of them are left to the LCS
+        let img2: Vec<u8> = (0..h)
+            .flat_map(|y| {
+                (0..w + 1).flat_map(move |x| match (x, y) {
+                    (_, 0) => [1, 2, 3, 255],
+                    (60, _) => [9, 9, 9, 255],
+                    (x, y) => pixel(if x > 60 { x - 1 } else { x }, y - 1),
+                })
+            })
+            .collect();
+
+        let layout = LayoutOption { columns: true };
+        let dimensions = (w as u32, h as u32);
+        let dimensions2 = ((w + 1) as u32, h as u32);
+        let result =
+            layout_diff(&img1, dimensions, &img2, dimensions2, Some(layout), None).unwrap();
+        assert_eq!(
+            result.row_bands,
+            vec![
+                Band::Inserted { start: 0, len: 1 },
+                Band::Removed {
+                    start: h as u32 - 1,
+                    len: 1
+                }
+            ]
+        );
+        assert_eq!(
+            result.column_bands,
+            vec![Band::Inserted { start: 60, len: 1 }]
+        );
+        assert_eq!(result.dimensions, (w as u32, h as u32 - 1));
+        assert_eq!(result.diff_count, 0);
+    }
+
+    #[test]
+    fn should_find_a_longest_common_subsequence() {
+        let sequences: [&[u8]; 6] = [b"", b"a", b"abcbdab", b"bdcaba", b"xaxbxcx", b"abcabcabc"];
+        for a in sequences {
+            for b in sequences {
+                // reference lengths from the full quadratic table
+                let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+                for i in 0..a.len() {
+                    for j in 0..b.len() {
+                        table[i + 1][j + 1] = if a[i] == b[j] {
+                            table[i][j] + 1
+                        } else {
+                            cmp::max(table[i][j + 1], table[i + 1][j])
+                        };
+                    }
+                }
+                let pairs = lcs(a.len(), b.len(), |i, j| a[i] == b[j]);
+                assert_eq!(pairs.len(), table[a.len()][b.len()]);
+                assert!(pairs.iter().all(|&(i, j)| a[i] == b[j]));
+                assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+            }
+        }
+    }
+
+    #[test]
+    fn should_align_rows_of_zero_width_images() {
+        let layout = LayoutOption { columns: true };
+        let img2 = image(1, &[10, 20, 30]);
+        let result = layout_diff(&[], (0, 3), &img2, (1, 3), Some(layout), None).unwrap();
+        assert_eq!(result.rows, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(
+            result.column_bands,
+            vec![Band::Inserted { start: 0, len: 1 }]
+        );
+        assert_eq!(result.dimensions, (0, 3));
+
+        let result = layout_diff(&[], (0, 3), &[], (0, 2), None, None).unwrap();
+        assert_eq!(result.row_bands, vec![Band::Removed { start: 2, len: 1 }]);
+    }
+}
@@ -1,10 +1,33 @@
 use core::cmp;
 
+pub mod align;
+#[cfg(feature = "png")]
+pub mod animation;
+pub mod annotate;
+pub mod baseline;
+pub mod comparator;
+pub mod composite;
+pub mod hash;
+pub mod layout;
+pub mod mask;
+#[cfg(feature = "png")]
+pub mod png_stream;
+pub mod region;
+pub mod stream;
+pub mod template;
+
+use hash::{composited_hash, HashAlgorithm, ImageHash};
+use mask::IgnoreMask;
+
 pub type Rgba = (u8, u8, u8, u8);
 pub type Rgb = (u8, u8, u8);
 
 pub static DEFAULT_DIFF_COLOR: Rgba = (255, 119, 119, 255);
 pub static DEFAULT_ANTI_ALIASED_COLOR: Rgba = (243, 156, 18, 255);
+pub static DEFAULT_SHIFTED_COLOR: Rgba = (52, 152, 219, 255);
+
+const WHITE: Rgb = (255, 255, 255);
+const BLACK: Rgb = (0, 0, 0);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 /// an error of pixelmatch
@@ -42,8 +65,161 @@ impl std::error::Error for PixelmatchError {
 pub struct PixelmatchOutput {
     /// difference count
     pub diff_count: usize,
+    /// count of differing pixels found nearby in the other image; see `shift_radius`
+    pub shifted_count: usize,
     /// a base rgba difference image data vec.
     pub diff_image: Vec<u8>,
+    /// a result of the perceptual hash pre-check, if `hash_check` option is given.
+    pub hash_check: Option<HashCheckOutput>,
+    /// translation of img2 relative to img1 found by `alignment`, (0, 0) without it;
+    /// img2 at `(x + dx, y + dy)` was compared with img1 at `(x, y)`.
+    pub offset: (i32, i32),
+}
+
+/// a global translation search run before the pixel-level comparison,
+/// so that a screenshot offset as a whole (scroll position, window chrome) is compared
+/// where it overlaps instead of differing everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    /// the largest offset to search in each direction
+    pub max_offset: u32,
+    /// how pixels of img1 not covered by the moved img2 are treated
+    pub border: BorderPolicy,
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Self {
+            max_offset: 32,
+            border: BorderPolicy::Ignore,
+        }
+    }
+}
+
+/// how pixels not covered by both images after alignment are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderPolicy {
+    /// drawn like similar pixels and not counted
+    #[default]
+    Ignore,
+    /// drawn in `diff_color` and counted as differences
+    Count,
+}
+
+/// how a pixel was classified by the comparison, as drawn into the diff image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PixelClass {
+    Similar,
+    AntiAliased,
+    Shifted,
+    Different,
+}
+
+/// a perceptual hash pre-check run before the pixel-level comparison.
+///
+/// if the hashes are close enough, the images are reported as identical
+/// and if they are far enough apart, as grossly different; in both cases the
+/// full pixel scan is skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashCheck {
+    /// a hash algorithm to compare images with
+    pub algorithm: HashAlgorithm,
+    /// hashes at most this many bits apart are treated as identical
+    pub identical_distance: u32,
+    /// hashes at least this many bits apart are treated as grossly different
+    pub gross_distance: u32,
+}
+
+impl Default for HashCheck {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::Difference,
+            identical_distance: 0,
+            gross_distance: 24,
+        }
+    }
+}
+
+/// a verdict of the perceptual hash pre-check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashVerdict {
+    /// hashes are within `identical_distance`; the pixel scan was skipped,
+    /// `diff_count` is 0 and `diff_image` is empty.
+    Identical,
+    /// hashes are in between; the pixel scan ran as usual.
+    Similar,
+    /// hashes are at least `gross_distance` apart; the pixel scan was skipped,
+    /// every pixel is counted in `diff_count` and `diff_image` is empty.
+    GrosslyDifferent,
+}
+
+/// an output of the perceptual hash pre-check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashCheckOutput {
+    /// hamming distance between the hashes of img1 and img2
+    pub distance: u32,
+    /// a verdict derived from the distance
+    pub verdict: HashVerdict,
+}
+
+/// a metric used to decide whether two pixels are different
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComparisonMode {
+    /// perceived color difference in YIQ color space
+    #[default]
+    Yiq,
+    /// brightness difference only; ignores hue and saturation changes.
+    /// `threshold` is a fraction of the full brightness range,
+    /// so 0.1 tolerates a brightness change of up to 25.5 levels.
+    Luminance,
+    /// raw channel difference; pixels differ only if any of R, G, B or A
+    /// differs by more than the given tolerance. `threshold`, `backdrop`
+    /// and alpha modes are not used.
+    ChannelTolerance(u8),
+}
+
+impl ComparisonMode {
+    /// maximum acceptable distance between two colors for the threshold
+    fn max_delta(self, threshold: f32) -> f32 {
+        match self {
+            // 35215 is the maximum possible value for the YIQ difference metric
+            ComparisonMode::Yiq => 35215.0 * threshold * threshold,
+            // 255 is the maximum possible brightness difference
+            ComparisonMode::Luminance => (255.0 * threshold) * (255.0 * threshold),
+            ComparisonMode::ChannelTolerance(tolerance) => tolerance as f32,
+        }
+    }
+}
+
+/// a background that semi-transparent pixels are composited over before comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backdrop {
+    /// a solid color
+    Solid(Rgb),
+    /// a checkerboard of `light` and `dark` squares, `size` pixels wide
+    Checkerboard { size: u32, light: Rgb, dark: Rgb },
+    /// composite over both white and black and take the larger difference;
+    /// catches changes hidden by either background
+    WhiteAndBlack,
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        Backdrop::Solid(WHITE)
+    }
+}
+
+/// how the alpha channel of an input image is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// color channels are independent of alpha
+    #[default]
+    Straight,
+    /// color channels are already multiplied by alpha, as produced by Skia, Cairo
+    /// and most GPU readbacks
+    Premultiplied,
+    /// alpha channel is ignored and every pixel is treated as opaque
+    Ignore,
 }
 
 /// an option for pixelmatch
@@ -53,10 +229,40 @@ pub struct PixelmatchOption {
     pub include_anti_alias: bool,
     /// matching threshold (0 to 1); smaller is more sensitive
     pub threshold: f32,
+    /// a metric used to compare pixels
+    pub comparison_mode: ComparisonMode,
+    /// a background that semi-transparent pixels are composited over
+    pub backdrop: Backdrop,
+    /// how the alpha channel of img1 is encoded
+    pub img1_alpha_mode: AlphaMode,
+    /// how the alpha channel of img2 is encoded
+    pub img2_alpha_mode: AlphaMode,
+    /// whether to decode sRGB into linear light before blending, and to compare the blended
+    /// channels encoded with the CIE L* curve instead of the sRGB one.
+    ///
+    /// channels stay scaled to 0-255, so `threshold` keeps its range and means
+    /// the same fraction of the largest possible difference, now measured in L*.
+    /// L* spaces dark tones further apart and light tones closer together than sRGB,
+    /// so the same threshold tolerates smaller sRGB steps in shadows and larger ones in highlights.
+    pub linear_light: bool,
     /// color of different pixels in diff output
     pub diff_color: Rgba,
     /// color of anti-aliased pixels in diff output
     pub anti_aliased_color: Rgba,
+    /// how far in pixels to look for a differing pixel in the other image; 0 disables the search.
+    ///
+    /// a differing pixel is counted as shifted instead of different when each image has
+    /// a matching pixel within this radius around the pixel of the other one,
+    /// so that content moved by a few pixels does not light up every edge.
+    pub shift_radius: u32,
+    /// color of shifted pixels in diff output
+    pub shifted_color: Rgba,
+    /// perceptual hash pre-check to skip the full scan for identical or grossly different images
+    pub hash_check: Option<HashCheck>,
+    /// global translation search to compare images offset as a whole where they overlap
+    pub alignment: Option<Alignment>,
+    /// pixels to leave out of the comparison; must be the size of the images
+    pub ignore_mask: Option<IgnoreMask>,
 }
 
 impl Default for PixelmatchOption {
@@ -64,8 +270,18 @@ impl Default for PixelmatchOption {
         Self {
             include_anti_alias: false,
             threshold: 0.1,
+            comparison_mode: ComparisonMode::Yiq,
+            backdrop: Backdrop::default(),
+            img1_alpha_mode: AlphaMode::Straight,
+            img2_alpha_mode: AlphaMode::Straight,
+            linear_light: false,
             diff_color: DEFAULT_DIFF_COLOR,
             anti_aliased_color: DEFAULT_ANTI_ALIASED_COLOR,
+            shift_radius: 0,
+            shifted_color: DEFAULT_SHIFTED_COLOR,
+            hash_check: None,
+            alignment: None,
+            ignore_mask: None,
         }
     }
 }
@@ -94,6 +310,28 @@ pub fn pixelmatch(
     img2: &[u8],
     dimensions: (u32, u32),
     options: Option<PixelmatchOption>,
+) -> Result<PixelmatchOutput, PixelmatchError> {
+    run(img1, img2, dimensions, options, None)
+}
+
+/// `pixelmatch` that also records the class of each pixel into `classes`;
+/// left empty if the hash pre-check skipped the scan.
+pub(crate) fn classify(
+    img1: &[u8],
+    img2: &[u8],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+    classes: &mut Vec<PixelClass>,
+) -> Result<PixelmatchOutput, PixelmatchError> {
+    run(img1, img2, dimensions, options, Some(classes))
+}
+
+fn run(
+    img1: &[u8],
+    img2: &[u8],
+    dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+    classes: Option<&mut Vec<PixelClass>>,
 ) -> Result<PixelmatchOutput, PixelmatchError> {
     if img1.len() != img2.len() {
         return Err(PixelmatchError::ImageLengthError);
@@ -103,43 +341,327 @@ pub fn pixelmatch(
     }
 
     let options = options.unwrap_or_default();
+    if let Some(mask) = &options.ignore_mask {
+        if mask.dimensions() != dimensions {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+    }
+    // the hash thumbnails and the alignment pyramids are sampled by `dimensions`
+    if (options.hash_check.is_some() || options.alignment.is_some())
+        && img1.len() != dimensions.0 as usize * dimensions.1 as usize * 4
+    {
+        return Err(PixelmatchError::InvalidFormatError);
+    }
 
-    // maximum acceptable square distance between two colors;
-    // 35215 is the maximum possible value for the YIQ difference metric
-    let threshold = options.threshold;
-    let max_delta = 35215.0 * threshold * threshold;
+    let compositor1 = Compositor::new(&options, options.img1_alpha_mode, dimensions.0);
+    let hash1 = options.hash_check.map(|check| {
+        composited_hash(
+            img1,
+            dimensions,
+            check.algorithm,
+            &compositor1,
+            options.ignore_mask.as_ref(),
+        )
+    });
+    Ok(compare(
+        (img1, &compositor1, hash1),
+        img2,
+        dimensions,
+        &options,
+        classes,
+    ))
+}
+
+/// the comparison behind `pixelmatch`, with the compositor and hash of img1 given
+/// so that they can be prepared once for many comparisons.
+/// the class of each pixel is recorded into `classes` if given and the pixels are scanned.
+fn compare(
+    (img1, compositor1, hash1): (&[u8], &Compositor, Option<ImageHash>),
+    img2: &[u8],
+    dimensions: (u32, u32),
+    options: &PixelmatchOption,
+    mut classes: Option<&mut Vec<PixelClass>>,
+) -> PixelmatchOutput {
+    if let Some(classes) = classes.as_mut() {
+        classes.clear();
+    }
+    let compositor2 = Compositor::new(options, options.img2_alpha_mode, dimensions.0);
+    let hash_check = options.hash_check.zip(hash1).map(|(check, hash1)| {
+        let hash2 = composited_hash(
+            img2,
+            dimensions,
+            check.algorithm,
+            &compositor2,
+            options.ignore_mask.as_ref(),
+        );
+        hash_verdict(hash1, hash2, &check)
+    });
+    let skipped_count = match hash_check.map(|c| c.verdict) {
+        Some(HashVerdict::Identical) => Some(0),
+        Some(HashVerdict::GrosslyDifferent) => Some(img1.len() / 4),
+        _ => None,
+    };
+    if let Some(diff_count) = skipped_count {
+        return PixelmatchOutput {
+            diff_count,
+            shifted_count: 0,
+            diff_image: vec![],
+            hash_check,
+            offset: (0, 0),
+        };
+    }
+
+    let offset = options.alignment.map_or((0, 0), |alignment| {
+        align::estimate(
+            (img1, compositor1),
+            (img2, &compositor2),
+            dimensions,
+            alignment.max_offset,
+        )
+    });
+    let aligned;
+    let img2 = if offset == (0, 0) {
+        img2
+    } else {
+        aligned = translate(img1, img2, dimensions, offset);
+        &aligned
+    };
+
+    // uncovered pixels are counted by their class, so they are recorded even if not asked for
+    let count_border = matches!(
+        options.alignment,
+        Some(Alignment {
+            border: BorderPolicy::Count,
+            ..
+        })
+    );
+    let mut border_classes = vec![];
+    let mut classes = match classes {
+        None if count_border => Some(&mut border_classes),
+        classes => classes,
+    };
+
+    // maximum acceptable distance between two colors
+    let max_delta = options.comparison_mode.max_delta(options.threshold);
     let mut diff_count = 0;
+    let mut shifted_count = 0;
     let mut diff_image: Vec<u8> = vec![0; img1.len()];
 
-    for y in 0..dimensions.1 {
-        for x in 0..dimensions.0 {
-            let pos = ((y * dimensions.0 + x) * 4) as usize;
-            // squared YUV distance between colors at this pixel position
-            let delta = color_delta(img1, img2, pos, pos, false);
-            if delta > max_delta {
-                // check it's a real rendering difference or just anti-aliasing
-                if options.include_anti_alias
-                    && (anti_aliased(img1, x as usize, y as usize, dimensions, img2)
-                        || anti_aliased(img2, x as usize, y as usize, dimensions, img1))
-                {
-                    // one of the pixels is anti-aliasing; draw as yellow and do not count as difference
-                    draw_pixel(&mut diff_image, pos, options.anti_aliased_color);
-                } else {
-                    // found substantial difference not caused by anti-aliasing; draw it as red
-                    draw_pixel(&mut diff_image, pos, options.diff_color);
+    if let Some(classes) = classes.as_mut() {
+        classes.resize(img1.len() / 4, PixelClass::Similar);
+    }
+
+    let width = dimensions.0 as usize;
+    if width > 0 {
+        for (y, diff_row) in diff_image.chunks_mut(width * 4).enumerate() {
+            let (diff, shifted) = compare_row(
+                (img1, compositor1),
+                (img2, &compositor2),
+                y,
+                dimensions,
+                options,
+                max_delta,
+                (
+                    diff_row,
+                    classes
+                        .as_mut()
+                        .map(|classes| &mut classes[y * width..(y + 1) * width]),
+                ),
+            );
+            diff_count += diff;
+            shifted_count += shifted;
+        }
+    }
+
+    if let Some(classes) = classes.filter(|_| count_border) {
+        for (i, class) in classes.iter_mut().enumerate() {
+            if covered(dimensions, offset, i % width, i / width) {
+                continue;
+            }
+            // uncovered pixels were compared with themselves, so they are rarely counted yet
+            match *class {
+                PixelClass::Different => {}
+                PixelClass::Shifted => {
+                    shifted_count -= 1;
                     diff_count += 1;
                 }
-            } else {
-                // pixels are similar; draw background as grayscale image blended with white
-                let y = blend(gray_pixel(img1, pos), 0.1);
-                draw_pixel(&mut diff_image, pos, (y, y, y, 255));
+                PixelClass::Similar | PixelClass::AntiAliased => diff_count += 1,
             }
+            *class = PixelClass::Different;
+            draw_pixel(&mut diff_image, i * 4, options.diff_color);
         }
     }
-    Ok(PixelmatchOutput {
+
+    PixelmatchOutput {
         diff_count,
+        shifted_count,
         diff_image,
-    })
+        hash_check,
+        offset,
+    }
+}
+
+/// moves img2 by `offset` onto the grid of img1;
+/// pixels not covered by img2 are taken from img1, so they compare as similar.
+fn translate(img1: &[u8], img2: &[u8], dimensions: (u32, u32), offset: (i32, i32)) -> Vec<u8> {
+    let (width, height) = (dimensions.0 as i64, dimensions.1 as i64);
+    let (dx, dy) = (offset.0 as i64, offset.1 as i64);
+    let mut aligned = img1.to_vec();
+    let (x0, x1) = (cmp::max(0, -dx), cmp::min(width, width - dx));
+    if x0 >= x1 {
+        return aligned;
+    }
+    for y in cmp::max(0, -dy)..cmp::min(height, height - dy) {
+        let dst = ((y * width + x0) * 4) as usize;
+        let src = (((y + dy) * width + x0 + dx) * 4) as usize;
+        let len = ((x1 - x0) * 4) as usize;
+        aligned[dst..dst + len].copy_from_slice(&img2[src..src + len]);
+    }
+    aligned
+}
+
+/// whether img2 moved by `offset` covers the pixel at `(x, y)` of img1
+fn covered(dimensions: (u32, u32), offset: (i32, i32), x: usize, y: usize) -> bool {
+    let x2 = x as i64 + offset.0 as i64;
+    let y2 = y as i64 + offset.1 as i64;
+    x2 >= 0 && x2 < dimensions.0 as i64 && y2 >= 0 && y2 < dimensions.1 as i64
+}
+
+/// compares row `y` of img1 and img2 and draws it into `diff_row`, and into `classes` if given;
+/// returns the difference count and the shifted count.
+/// the images may be a window of rows of larger images as long as it holds the rows around `y`
+/// that the anti-aliasing detection and the shift search look at.
+fn compare_row(
+    (img1, compositor1): (&[u8], &Compositor),
+    (img2, compositor2): (&[u8], &Compositor),
+    y: usize,
+    dimensions: (u32, u32),
+    options: &PixelmatchOption,
+    max_delta: f32,
+    (diff_row, mut classes): (&mut [u8], Option<&mut [PixelClass]>),
+) -> (usize, usize) {
+    let width = dimensions.0 as usize;
+    let radius = options.shift_radius as usize;
+    let row = y * width * 4..(y + 1) * width * 4;
+    // most rows of screenshots are identical; all of their pixels are similar
+    // as long as both images are composited the same way
+    if compositor1.alpha_mode == compositor2.alpha_mode && img1[row.clone()] == img2[row] {
+        for x in 0..width {
+            let gray = blend(compositor1.gray(img1, (y * width + x) * 4), 0.1, 255);
+            draw_pixel(diff_row, x * 4, (gray, gray, gray, 255));
+        }
+        if let Some(classes) = classes {
+            classes.fill(PixelClass::Similar);
+        }
+        return (0, 0);
+    }
+    let mut diff_count = 0;
+    let mut shifted_count = 0;
+    for x in 0..width {
+        let pos = (y * width + x) * 4;
+        let ignored = options.ignore_mask.as_ref().map_or(false, |mask| {
+            mask.is_ignored(x as u32, (compositor1.first_row + y) as u32)
+        });
+        // distance between colors at this pixel position; ignored pixels are drawn as similar
+        let delta = if ignored {
+            0.0
+        } else {
+            pixel_delta(
+                (img1, compositor1),
+                (img2, compositor2),
+                pos,
+                pos,
+                options.comparison_mode,
+            )
+        };
+        let class = if delta > max_delta {
+            // check it's a real rendering difference or just anti-aliasing
+            if options.include_anti_alias
+                && (anti_aliased((img1, compositor1), x, y, dimensions, (img2, compositor2))
+                    || anti_aliased((img2, compositor2), x, y, dimensions, (img1, compositor1)))
+            {
+                // one of the pixels is anti-aliasing; draw as yellow and do not count as difference
+                draw_pixel(diff_row, x * 4, options.anti_aliased_color);
+                PixelClass::AntiAliased
+            } else if radius > 0
+                && shifted(
+                    (img1, compositor1),
+                    (img2, compositor2),
+                    (x, y),
+                    dimensions,
+                    radius,
+                    options.comparison_mode,
+                    max_delta,
+                )
+                && shifted(
+                    (img2, compositor2),
+                    (img1, compositor1),
+                    (x, y),
+                    dimensions,
+                    radius,
+                    options.comparison_mode,
+                    max_delta,
+                )
+            {
+                // the same colors are found nearby in both images; the content just moved
+                draw_pixel(diff_row, x * 4, options.shifted_color);
+                shifted_count += 1;
+                PixelClass::Shifted
+            } else {
+                // found substantial difference not caused by anti-aliasing; draw it as red
+                draw_pixel(diff_row, x * 4, options.diff_color);
+                diff_count += 1;
+                PixelClass::Different
+            }
+        } else {
+            // pixels are similar; draw background as grayscale image blended with white
+            let y = blend(compositor1.gray(img1, pos), 0.1, 255);
+            draw_pixel(diff_row, x * 4, (y, y, y, 255));
+            PixelClass::Similar
+        };
+        if let Some(classes) = classes.as_mut() {
+            classes[x] = class;
+        }
+    }
+    (diff_count, shifted_count)
+}
+
+/// check if the pixel at `(x1, y1)` of img1 has a similar pixel within `radius` in img2
+fn shifted(
+    (img1, compositor1): (&[u8], &Compositor),
+    (img2, compositor2): (&[u8], &Compositor),
+    (x1, y1): (usize, usize),
+    dimensions: (u32, u32),
+    radius: usize,
+    mode: ComparisonMode,
+    max_delta: f32,
+) -> bool {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let pos1 = (y1 * width + x1) * 4;
+    for y in y1.saturating_sub(radius)..=cmp::min(y1 + radius, height - 1) {
+        for x in x1.saturating_sub(radius)..=cmp::min(x1 + radius, width - 1) {
+            let pos2 = (y * width + x) * 4;
+            if pos2 != pos1
+                && pixel_delta((img1, compositor1), (img2, compositor2), pos1, pos2, mode)
+                    <= max_delta
+            {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn hash_verdict(hash1: ImageHash, hash2: ImageHash, check: &HashCheck) -> HashCheckOutput {
+    let distance = hash1.distance(&hash2);
+    let verdict = if distance <= check.identical_distance {
+        HashVerdict::Identical
+    } else if distance >= check.gross_distance {
+        HashVerdict::GrosslyDifferent
+    } else {
+        HashVerdict::Similar
+    };
+    HashCheckOutput { distance, verdict }
 }
 
 fn draw_pixel(diff_buf: &mut [u8], pos: usize, rgba: Rgba) {
@@ -149,64 +671,310 @@ fn draw_pixel(diff_buf: &mut [u8], pos: usize, rgba: Rgba) {
     diff_buf[pos + 3] = rgba.3;
 }
 
-fn gray_pixel(img: &[u8], pos: usize) -> u8 {
-    let a = img[pos + 3] as f32 / 255.0;
-    let r = blend(img[pos], a);
-    let g = blend(img[pos + 1], a);
-    let b = blend(img[pos + 2], a);
-    rgb2y(r, g, b) as u8
+/// turns possibly semi-transparent pixels of an image into opaque colors
+/// by compositing them over a backdrop
+#[derive(Debug, Clone)]
+struct Compositor {
+    backdrop: Backdrop,
+    alpha_mode: AlphaMode,
+    width: usize,
+    /// index of the first row of the image data in the whole image, when comparing a window of rows
+    first_row: usize,
+    /// sRGB to linear light lookup table, if comparing in linear light
+    linear: Option<[f32; 256]>,
+    /// per-pixel values of the one image this compositor was prepared for
+    prepared: Option<Prepared>,
 }
 
-// calculate color difference according to the paper "Measuring perceived color difference
-// using YIQ NTSC transmission color space in mobile applications" by Y. Kotsarenko and F. Ramos
-fn color_delta(img1: &[u8], img2: &[u8], pos1: usize, pos2: usize, only_brightness: bool) -> f32 {
-    let a1 = img1[pos1 + 3] as f32 / 255.0;
-    let a2 = img2[pos2 + 3] as f32 / 255.0;
+/// per-pixel values of an image that don't depend on the image it is compared with
+#[derive(Clone)]
+struct Prepared {
+    /// YIQ over the backdrop of each pixel; over white for `Backdrop::WhiteAndBlack`
+    yiq: Vec<(f32, f32, f32)>,
+    /// YIQ over black, for `Backdrop::WhiteAndBlack` only
+    yiq_black: Vec<(f32, f32, f32)>,
+    /// whether each pixel has 3+ adjacent pixels of the same color
+    many_siblings: Vec<bool>,
+    /// grayscale value of each pixel for the diff image background
+    gray: Vec<u8>,
+}
 
-    let r1 = blend(img1[pos1], a1);
-    let g1 = blend(img1[pos1 + 1], a1);
-    let b1 = blend(img1[pos1 + 2], a1);
+impl std::fmt::Debug for Prepared {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Prepared")
+            .field("pixels", &self.gray.len())
+            .finish()
+    }
+}
+
+impl Compositor {
+    fn new(options: &PixelmatchOption, alpha_mode: AlphaMode, width: u32) -> Self {
+        Self {
+            backdrop: options.backdrop,
+            alpha_mode,
+            width: width as usize,
+            first_row: 0,
+            linear: if options.linear_light {
+                let mut lut = [0.0; 256];
+                for (c, v) in lut.iter_mut().enumerate() {
+                    *v = srgb_to_linear(c as f32 / 255.0);
+                }
+                Some(lut)
+            } else {
+                None
+            },
+            prepared: None,
+        }
+    }
 
-    let r2 = blend(img2[pos2], a2);
-    let g2 = blend(img2[pos2 + 1], a2);
-    let b2 = blend(img2[pos2 + 2], a2);
+    /// computes the per-pixel values of a whole image once, for comparing it many times.
+    /// the compositor must then only be used with that image.
+    fn prepare(&mut self, img: &[u8], dimensions: (u32, u32)) {
+        self.prepared = None;
+        let (width, len) = (dimensions.0 as usize, img.len() / 4);
+        let black = matches!(self.backdrop, Backdrop::WhiteAndBlack);
+        let prepared = Prepared {
+            yiq: (0..len).map(|i| self.yiq(img, i * 4, false)).collect(),
+            yiq_black: if black {
+                (0..len).map(|i| self.yiq(img, i * 4, true)).collect()
+            } else {
+                vec![]
+            },
+            many_siblings: (0..len)
+                .map(|i| {
+                    has_many_siblings(
+                        (img, self),
+                        i % width,
+                        i / width,
+                        dimensions.0,
+                        dimensions.1,
+                    )
+                })
+                .collect(),
+            gray: (0..len).map(|i| self.gray(img, i * 4)).collect(),
+        };
+        self.prepared = Some(prepared);
+    }
 
-    let y = rgb2y(r1, g1, b1) - rgb2y(r2, g2, b2);
+    /// backdrop color behind the pixel at `pos`; white for `Backdrop::WhiteAndBlack`
+    fn backdrop_at(&self, pos: usize) -> Rgb {
+        match self.backdrop {
+            Backdrop::Solid(color) => color,
+            Backdrop::Checkerboard { size, light, dark } => {
+                let size = cmp::max(size as usize, 1);
+                let (x, y) = (
+                    (pos / 4) % self.width,
+                    (pos / 4) / self.width + self.first_row,
+                );
+                if (x / size + y / size) % 2 == 0 {
+                    light
+                } else {
+                    dark
+                }
+            }
+            Backdrop::WhiteAndBlack => WHITE,
+        }
+    }
+
+    fn color(&self, img: &[u8], pos: usize, bg: Rgb) -> (f32, f32, f32) {
+        if let Some(lut) = &self.linear {
+            return self.linear_color(lut, img, pos, bg);
+        }
+        let a = img[pos + 3] as f32 / 255.0;
+        let composite = |c: u8, bg: u8| match self.alpha_mode {
+            AlphaMode::Straight => blend(c, a, bg),
+            // the color already carries its share; only the backdrop's share is left to add
+            AlphaMode::Premultiplied => (c as f32 + bg as f32 * (1.0 - a)) as u8,
+            AlphaMode::Ignore => c,
+        };
+        (
+            composite(img[pos], bg.0) as f32,
+            composite(img[pos + 1], bg.1) as f32,
+            composite(img[pos + 2], bg.2) as f32,
+        )
+    }
+
+    /// same as `color`, but blends in linear light and returns the result encoded
+    /// with the CIE L* curve, scaled to 0-255
+    fn linear_color(&self, lut: &[f32; 256], img: &[u8], pos: usize, bg: Rgb) -> (f32, f32, f32) {
+        let a = img[pos + 3] as f32 / 255.0;
+        let composite = |c: u8, bg: u8| {
+            let bg = lut[bg as usize];
+            let c = match self.alpha_mode {
+                AlphaMode::Straight => lut[c as usize] * a + bg * (1.0 - a),
+                // unpremultiply first; premultiplied data is encoded in sRGB as well
+                AlphaMode::Premultiplied if a > 0.0 => {
+                    srgb_to_linear(f32::min(c as f32 / 255.0 / a, 1.0)) * a + bg * (1.0 - a)
+                }
+                AlphaMode::Premultiplied => bg,
+                AlphaMode::Ignore => lut[c as usize],
+            };
+            linear_to_lightness(c) * 255.0
+        };
+        (
+            composite(img[pos], bg.0),
+            composite(img[pos + 1], bg.1),
+            composite(img[pos + 2], bg.2),
+        )
+    }
+
+    /// YIQ of the pixel at `pos` over its backdrop,
+    /// or over black for the second pass of `Backdrop::WhiteAndBlack` if `black` is set
+    fn yiq(&self, img: &[u8], pos: usize, black: bool) -> (f32, f32, f32) {
+        if let Some(prepared) = &self.prepared {
+            return if black {
+                prepared.yiq_black[pos / 4]
+            } else {
+                prepared.yiq[pos / 4]
+            };
+        }
+        let bg = if black { BLACK } else { self.backdrop_at(pos) };
+        rgb2yiq(self.color(img, pos, bg))
+    }
+
+    /// grayscale value for the diff image background, always encoded in sRGB for display
+    fn gray(&self, img: &[u8], pos: usize) -> u8 {
+        if let Some(prepared) = &self.prepared {
+            return prepared.gray[pos / 4];
+        }
+        let (r, g, b) = self.color(img, pos, self.backdrop_at(pos));
+        let y = rgb2y(r, g, b);
+        if self.linear.is_some() {
+            (linear_to_srgb(lightness_to_linear(y / 255.0)) * 255.0) as u8
+        } else {
+            y as u8
+        }
+    }
+}
+
+// decode a sRGB component (0 to 1) into linear light (0 to 1)
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// encode a linear light component (0 to 1) into sRGB (0 to 1)
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// encode linear light (0 to 1) with the CIE L* curve, as L* / 100 (0 to 1)
+fn linear_to_lightness(y: f32) -> f32 {
+    if y > 216.0 / 24389.0 {
+        1.16 * y.cbrt() - 0.16
+    } else {
+        y * 24389.0 / 2700.0
+    }
+}
+
+// decode L* / 100 (0 to 1) into linear light (0 to 1)
+fn lightness_to_linear(l: f32) -> f32 {
+    if l > 0.08 {
+        ((l + 0.16) / 1.16).powi(3)
+    } else {
+        l * 2700.0 / 24389.0
+    }
+}
+
+fn pixel_delta(
+    (img1, compositor1): (&[u8], &Compositor),
+    (img2, compositor2): (&[u8], &Compositor),
+    pos1: usize,
+    pos2: usize,
+    mode: ComparisonMode,
+) -> f32 {
+    let only_brightness = match mode {
+        ComparisonMode::Yiq => false,
+        ComparisonMode::Luminance => true,
+        // largest per-channel distance
+        ComparisonMode::ChannelTolerance(_) => {
+            return (0..4)
+                .map(|i| img1[pos1 + i].abs_diff(img2[pos2 + i]))
+                .max()
+                .unwrap_or(0) as f32
+        }
+    };
+    // squared YUV or brightness distance over the backdrops, or over black
+    let delta = |black: bool| {
+        squared_delta(
+            compositor1.yiq(img1, pos1, black),
+            compositor2.yiq(img2, pos2, black),
+            only_brightness,
+        )
+    };
+    match compositor1.backdrop {
+        Backdrop::WhiteAndBlack => f32::max(delta(false), delta(true)),
+        _ => delta(false),
+    }
+}
+
+/// squared YUV or brightness distance between two colors in YIQ,
+/// on the scale `ComparisonMode::max_delta` thresholds
+fn squared_delta(yiq1: (f32, f32, f32), yiq2: (f32, f32, f32), only_brightness: bool) -> f32 {
+    let delta = color_delta(yiq1, yiq2, only_brightness);
+    if only_brightness {
+        delta * delta
+    } else {
+        delta
+    }
+}
+
+// calculate color difference according to the paper "Measuring perceived color difference
+// using YIQ NTSC transmission color space in mobile applications" by Y. Kotsarenko and F. Ramos
+fn color_delta(yiq1: (f32, f32, f32), yiq2: (f32, f32, f32), only_brightness: bool) -> f32 {
+    let y = yiq1.0 - yiq2.0;
 
     if only_brightness {
         return y;
     }
 
-    let i = rgb2i(r1, g1, b1) - rgb2i(r2, g2, b2);
-    let q = rgb2q(r1, g1, b1) - rgb2q(r2, g2, b2);
+    let i = yiq1.1 - yiq2.1;
+    let q = yiq1.2 - yiq2.2;
 
     0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
 }
 
-// blend semi-transparent color with white
-fn blend(c: u8, a: f32) -> u8 {
-    (255.0 + ((c as i32 - 255) as f32) * a) as u8
+// blend semi-transparent color with a background color
+fn blend(c: u8, a: f32, bg: u8) -> u8 {
+    (bg as f32 + ((c as i32 - bg as i32) as f32) * a) as u8
 }
 
-fn rgb2y(r: u8, g: u8, b: u8) -> f32 {
-    r as f32 * 0.298_895_3 + g as f32 * 0.586_622_4 + b as f32 * 0.114_482_23
+fn rgb2yiq((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    (rgb2y(r, g, b), rgb2i(r, g, b), rgb2q(r, g, b))
 }
-fn rgb2i(r: u8, g: u8, b: u8) -> f32 {
-    r as f32 * 0.595_977_99 - g as f32 * 0.274_176_1 - b as f32 * 0.321_801_8
+
+fn rgb2y(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.298_895_3 + g * 0.586_622_4 + b * 0.114_482_23
+}
+fn rgb2i(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_8
 }
-fn rgb2q(r: u8, g: u8, b: u8) -> f32 {
-    r as f32 * 0.211_470_17 - g as f32 * 0.522_617_1 + b as f32 * 0.311_146_9
+fn rgb2q(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_9
 }
 
 /// check if a pixel is likely a part of anti-aliasing;
 /// based on "Anti-aliased Pixel and Intensity Slope Detector" paper by V. Vysniauskas, 2009
 /// http://eejournal.ktu.lt/index.php/elt/article/view/10058/5000
-fn anti_aliased(img1: &[u8], x1: usize, y1: usize, dimensions: (u32, u32), img2: &[u8]) -> bool {
-    let x0 = cmp::max(x1 as i32 - 1, 0) as usize;
-    let y0 = cmp::max(y1 as i32 - 1, 0) as usize;
+fn anti_aliased(
+    (img1, compositor1): (&[u8], &Compositor),
+    x1: usize,
+    y1: usize,
+    dimensions: (u32, u32),
+    (img2, compositor2): (&[u8], &Compositor),
+) -> bool {
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
 
-    let x2 = cmp::min(x1 as i32 + 1, dimensions.0 as i32 - 1) as usize;
-    let y2 = cmp::min(y1 as i32 + 1, dimensions.1 as i32 - 1) as usize;
+    let x2 = cmp::min(x1 + 1, dimensions.0 as usize - 1);
+    let y2 = cmp::min(y1 + 1, dimensions.1 as usize - 1);
 
     let pos = (y1 * dimensions.0 as usize + x1) * 4;
     let mut zeroes = if x1 == x0 || x1 == x2 || y1 == y0 || y1 == y2 {
@@ -232,11 +1000,10 @@ fn anti_aliased(img1: &[u8], x1: usize, y1: usize, dimensions: (u32, u32), img2:
             }
 
             // brightness delta between the center pixel and adjacent one
+            let pos2 = (y * width as usize + x) * 4;
             let delta = color_delta(
-                img1,
-                img1,
-                pos,
-                ((y * width as usize + x) * 4) as usize,
+                compositor1.yiq(img1, pos, false),
+                compositor1.yiq(img1, pos2, false),
                 true,
             ) as i32;
 
@@ -268,16 +1035,25 @@ fn anti_aliased(img1: &[u8], x1: usize, y1: usize, dimensions: (u32, u32), img2:
 
     // if either the darkest or the brightest pixel has 3+ equal siblings in both images
     // (definitely not anti-aliased), this pixel is anti-aliased
-    (has_many_siblings(img1, min_x, min_y, width, height)
-        && has_many_siblings(img2, min_x, min_y, width, height))
-        || (has_many_siblings(img1, max_x, max_y, width, height)
-            && has_many_siblings(img2, max_x, max_y, width, height))
+    (has_many_siblings((img1, compositor1), min_x, min_y, width, height)
+        && has_many_siblings((img2, compositor2), min_x, min_y, width, height))
+        || (has_many_siblings((img1, compositor1), max_x, max_y, width, height)
+            && has_many_siblings((img2, compositor2), max_x, max_y, width, height))
 }
 
 /// check if a pixel has 3+ adjacent pixels of the same color.
-fn has_many_siblings(img: &[u8], x1: usize, y1: usize, width: u32, height: u32) -> bool {
-    let x0 = cmp::max(x1 - 1, 0);
-    let y0 = cmp::max(y1 - 1, 0);
+fn has_many_siblings(
+    (img, compositor): (&[u8], &Compositor),
+    x1: usize,
+    y1: usize,
+    width: u32,
+    height: u32,
+) -> bool {
+    if let Some(prepared) = &compositor.prepared {
+        return prepared.many_siblings[y1 * width as usize + x1];
+    }
+    let x0 = x1.saturating_sub(1);
+    let y0 = y1.saturating_sub(1);
     let x2 = cmp::min(x1 + 1, width as usize - 1);
     let y2 = cmp::min(y1 + 1, height as usize - 1);
     let pos = (y1 * width as usize + x1) * 4;
@@ -324,3 +1100,289 @@ fn should_detect_1pixel_diff() {
         vec![255, 119, 119, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
     );
 }
+
+#[test]
+fn should_skip_scan_when_hashes_are_identical() {
+    let img = vec![255; 16 * 16 * 4];
+    let result = pixelmatch(
+        &img,
+        &img,
+        (16, 16),
+        Some(PixelmatchOption {
+            hash_check: Some(HashCheck::default()),
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(result.diff_count, 0);
+    assert!(result.diff_image.is_empty());
+    assert_eq!(
+        result.hash_check,
+        Some(HashCheckOutput {
+            distance: 0,
+            verdict: HashVerdict::Identical
+        })
+    );
+}
+
+#[test]
+fn should_leave_masked_pixels_out_of_the_hash_check() {
+    let (w, h) = (16, 16);
+    let img1: Vec<u8> = (0..w * h)
+        .flat_map(|i| [(i % w * 16) as u8, 0, 0, 255])
+        .collect();
+    // a spinner covering the left half, drawn as the inverted gradient
+    let mut img2 = img1.clone();
+    for i in (0..w * h).filter(|i| i % w < w / 2) {
+        img2[i * 4] = 255 - img2[i * 4];
+    }
+    let options = PixelmatchOption {
+        hash_check: Some(HashCheck {
+            gross_distance: 8,
+            ..HashCheck::default()
+        }),
+        ..PixelmatchOption::default()
+    };
+    let unmasked = pixelmatch(&img1, &img2, (w as u32, h as u32), Some(options.clone())).unwrap();
+    assert_eq!(
+        unmasked.hash_check.map(|c| c.verdict),
+        Some(HashVerdict::GrosslyDifferent)
+    );
+    assert_eq!(unmasked.diff_count, w * h);
+
+    let spinner: Vec<Vec<u8>> = [0u8, 255]
+        .iter()
+        .map(|&v| {
+            (0..w * h)
+                .flat_map(|i| [if i % w < w / 2 { v } else { 0 }, 0, 0, 255])
+                .collect()
+        })
+        .collect();
+    let mask = IgnoreMask::learn(
+        &[&spinner[0], &spinner[1]],
+        (w as u32, h as u32),
+        Some(mask::MaskOption { dilation: 0 }),
+        None,
+    )
+    .unwrap();
+    let masked = pixelmatch(
+        &img1,
+        &img2,
+        (w as u32, h as u32),
+        Some(PixelmatchOption {
+            ignore_mask: Some(mask),
+            ..options
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        masked.hash_check.map(|c| c.verdict),
+        Some(HashVerdict::Identical)
+    );
+}
+
+#[test]
+fn should_ignore_hue_change_in_luminance_mode() {
+    // pure red and a gray of the same brightness
+    let img1 = vec![255, 0, 0, 255];
+    let img2 = vec![76, 76, 76, 255];
+    let yiq = pixelmatch(&img1, &img2, (1, 1), None).unwrap();
+    assert_eq!(yiq.diff_count, 1);
+    let luminance = pixelmatch(
+        &img1,
+        &img2,
+        (1, 1),
+        Some(PixelmatchOption {
+            comparison_mode: ComparisonMode::Luminance,
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(luminance.diff_count, 0);
+}
+
+#[test]
+fn should_weight_dark_tones_in_linear_light() {
+    // a light-tone step and a dark-tone step of the same size in sRGB
+    let img1 = vec![200, 200, 200, 255, 20, 20, 20, 255];
+    let img2 = vec![230, 230, 230, 255, 50, 50, 50, 255];
+    let options = |linear_light| {
+        Some(PixelmatchOption {
+            comparison_mode: ComparisonMode::Luminance,
+            linear_light,
+            threshold: 0.12,
+            ..PixelmatchOption::default()
+        })
+    };
+    let is_diff = |output: PixelmatchOutput| {
+        output
+            .diff_image
+            .chunks(4)
+            .map(|p| p == [255, 119, 119, 255])
+            .collect::<Vec<bool>>()
+    };
+    let srgb = pixelmatch(&img1, &img2, (2, 1), options(false)).unwrap();
+    assert_eq!(is_diff(srgb), [false, false]);
+    let linear = pixelmatch(&img1, &img2, (2, 1), options(true)).unwrap();
+    assert_eq!(is_diff(linear), [false, true]);
+}
+
+#[test]
+fn should_tolerate_channel_jitter_in_channel_tolerance_mode() {
+    let img1 = vec![100, 100, 100, 255, 100, 100, 100, 255];
+    let img2 = vec![102, 98, 100, 253, 100, 100, 103, 255];
+    let result = pixelmatch(
+        &img1,
+        &img2,
+        (2, 1),
+        Some(PixelmatchOption {
+            comparison_mode: ComparisonMode::ChannelTolerance(2),
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(result.diff_count, 1);
+    assert_eq!(result.diff_image[4..8], [255, 119, 119, 255]);
+}
+
+#[test]
+fn should_detect_transparency_change_over_white_and_black() {
+    // half transparent white and fully transparent pixels look the same over white
+    let img1 = vec![255, 255, 255, 128];
+    let img2 = vec![255, 255, 255, 0];
+    let white = pixelmatch(&img1, &img2, (1, 1), None).unwrap();
+    assert_eq!(white.diff_count, 0);
+    let both = pixelmatch(
+        &img1,
+        &img2,
+        (1, 1),
+        Some(PixelmatchOption {
+            backdrop: Backdrop::WhiteAndBlack,
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(both.diff_count, 1);
+}
+
+#[test]
+fn should_compare_premultiplied_input_with_straight_input() {
+    // half transparent white, premultiplied and straight
+    let img1 = vec![128, 128, 128, 128];
+    let img2 = vec![255, 255, 255, 128];
+    let straight = pixelmatch(&img1, &img2, (1, 1), None).unwrap();
+    assert_eq!(straight.diff_count, 1);
+    let premultiplied = pixelmatch(
+        &img1,
+        &img2,
+        (1, 1),
+        Some(PixelmatchOption {
+            img1_alpha_mode: AlphaMode::Premultiplied,
+            backdrop: Backdrop::WhiteAndBlack,
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(premultiplied.diff_count, 0);
+}
+
+#[test]
+fn should_compare_identical_bytes_read_in_different_alpha_modes() {
+    let img = vec![128, 0, 0, 128, 0, 0, 0, 255];
+    let same = pixelmatch(&img, &img, (2, 1), None).unwrap();
+    assert_eq!(same.diff_count, 0);
+    let mixed = pixelmatch(
+        &img,
+        &img,
+        (2, 1),
+        Some(PixelmatchOption {
+            img1_alpha_mode: AlphaMode::Premultiplied,
+            ..PixelmatchOption::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(mixed.diff_count, 1);
+    assert_eq!(mixed.diff_image[4..], same.diff_image[4..]);
+}
+
+#[test]
+fn should_count_content_moved_by_a_pixel_as_shifted() {
+    let (w, h) = (5, 3);
+    let line = |x: usize| {
+        let mut img = vec![255; w * h * 4];
+        for y in 0..h {
+            img[(y * w + x) * 4..(y * w + x) * 4 + 3].copy_from_slice(&[0, 0, 0]);
+        }
+        img
+    };
+    let (img1, img2) = (line(1), line(2));
+    let strict = pixelmatch(&img1, &img2, (w as u32, h as u32), None).unwrap();
+    assert_eq!(strict.diff_count, 6);
+    assert_eq!(strict.shifted_count, 0);
+
+    let options = PixelmatchOption {
+        shift_radius: 1,
+        ..PixelmatchOption::default()
+    };
+    let tolerant = pixelmatch(&img1, &img2, (w as u32, h as u32), Some(options)).unwrap();
+    assert_eq!(tolerant.diff_count, 0);
+    assert_eq!(tolerant.shifted_count, 6);
+    assert_eq!(&tolerant.diff_image[4..8], &[52, 152, 219, 255]);
+}
+
+#[test]
+fn should_compare_aligned_overlap_of_scrolled_images() {
+    let (w, h) = (40usize, 30usize);
+    let scene = |x: usize, y: usize| [(x * 13 + y * 7) as u8, (x * y) as u8, (y * 29) as u8, 255];
+    let img1: Vec<u8> = (0..w * h).flat_map(|i| scene(i % w, i / w)).collect();
+    // scrolled down by 2 rows; the first rows of img2 show new content
+    let img2: Vec<u8> = (0..w * h)
+        .flat_map(|i| match i / w {
+            y if y < 2 => [0, 0, 0, 255],
+            y => scene(i % w, y - 2),
+        })
+        .collect();
+    let dimensions = (w as u32, h as u32);
+    assert!(
+        pixelmatch(&img1, &img2, dimensions, None)
+            .unwrap()
+            .diff_count
+            > 0
+    );
+
+    let options = PixelmatchOption {
+        alignment: Some(Alignment::default()),
+        ..PixelmatchOption::default()
+    };
+    let ignored = pixelmatch(&img1, &img2, dimensions, Some(options.clone())).unwrap();
+    assert_eq!(ignored.offset, (0, 2));
+    assert_eq!(ignored.diff_count, 0);
+
+    let options = PixelmatchOption {
+        alignment: Some(Alignment {
+            border: BorderPolicy::Count,
+            ..Alignment::default()
+        }),
+        ..options
+    };
+    let counted = pixelmatch(&img1, &img2, dimensions, Some(options.clone())).unwrap();
+    assert_eq!(counted.diff_count, 2 * w);
+
+    // an uncovered pixel drawn like diff_color is still counted once
+    let pos = (h - 1) * w * 4;
+    let drawn = &ignored.diff_image[pos..pos + 4];
+    let options = PixelmatchOption {
+        diff_color: (drawn[0], drawn[1], drawn[2], drawn[3]),
+        ..options
+    };
+    let mut classes = vec![];
+    let counted = classify(&img1, &img2, dimensions, Some(options), &mut classes).unwrap();
+    assert_eq!(counted.diff_count, 2 * w);
+    assert_eq!(
+        classes
+            .iter()
+            .filter(|c| **c == PixelClass::Different)
+            .count(),
+        2 * w
+    );
+}
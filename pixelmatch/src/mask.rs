@@ -0,0 +1,340 @@
+use super::{pixel_delta, Compositor, PixelmatchError, PixelmatchOption};
+
+/// the first bytes of a serialized mask
+const MAGIC: &[u8; 4] = b"PMSK";
+
+/// an option for IgnoreMask::learn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskOption {
+    /// how far in pixels to grow the varying area, so that its edges are ignored too
+    pub dilation: u32,
+}
+
+impl Default for MaskOption {
+    fn default() -> Self {
+        Self { dilation: 2 }
+    }
+}
+
+/// a set of pixels to leave out of a comparison, e.g. carets, spinners and relative dates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoreMask {
+    dimensions: (u32, u32),
+    /// ignored pixels as sorted, non-adjacent ranges of pixel indices, so that the size
+    /// of the mask depends on how many areas it covers rather than on the image size
+    ranges: Vec<(u64, u64)>,
+}
+
+impl IgnoreMask {
+    /// learns a mask from several renders of the same page: a pixel is ignored
+    /// if it differs between the first render and any other one, or lies within
+    /// `dilation` pixels of such a pixel.
+    /// all renders are read with `img2_alpha_mode`, since they come from the same renderer.
+    ///
+    /// # Arguments
+    ///
+    /// * `renders` - rgba 24bit image data slices of the same size.
+    /// * `dimensions` - a size of the renders.
+    /// * `mask` - an option for learning.
+    /// * `options` - an option for pixelmatch; its threshold and metric decide which pixels vary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pixelmatch::mask::*;
+    ///
+    /// let render1 = vec![255; 3 * 3 * 4];
+    /// let mut render2 = render1.clone();
+    /// render2[..3].copy_from_slice(&[0, 0, 0]);
+    /// let mask = IgnoreMask::learn(&[&render1, &render2], (3, 3), None, None).unwrap();
+    /// assert!(mask.is_ignored(2, 2));
+    /// let mask = IgnoreMask::from_rle(&mask.to_rle()).unwrap();
+    /// assert_eq!(mask.ignored_count(), 9);
+    /// ```
+    pub fn learn(
+        renders: &[&[u8]],
+        dimensions: (u32, u32),
+        mask: Option<MaskOption>,
+        options: Option<PixelmatchOption>,
+    ) -> Result<Self, PixelmatchError> {
+        let len = dimensions.0 as usize * dimensions.1 as usize;
+        if renders.iter().any(|render| render.len() != len * 4) {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+        let mask = mask.unwrap_or_default();
+        let options = options.unwrap_or_default();
+        let max_delta = options.comparison_mode.max_delta(options.threshold);
+        let compositor = Compositor::new(&options, options.img2_alpha_mode, dimensions.0);
+
+        let mut ignored = vec![false; len];
+        if let Some((first, rest)) = renders.split_first() {
+            for (i, ignored) in ignored.iter_mut().enumerate() {
+                *ignored = rest.iter().any(|render| {
+                    pixel_delta(
+                        (first, &compositor),
+                        (render, &compositor),
+                        i * 4,
+                        i * 4,
+                        options.comparison_mode,
+                    ) > max_delta
+                });
+            }
+        }
+        dilate(&mut ignored, dimensions, mask.dilation as usize);
+        let mut ranges: Vec<(u64, u64)> = vec![];
+        for (i, _) in ignored.iter().enumerate().filter(|(_, &ignored)| ignored) {
+            match ranges.last_mut() {
+                Some(last) if last.1 == i as u64 => last.1 += 1,
+                _ => ranges.push((i as u64, i as u64 + 1)),
+            }
+        }
+        Ok(Self { dimensions, ranges })
+    }
+
+    /// a size of the mask
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// whether the pixel at `(x, y)` is ignored; pixels outside the mask are not
+    pub fn is_ignored(&self, x: u32, y: u32) -> bool {
+        if x >= self.dimensions.0 || y >= self.dimensions.1 {
+            return false;
+        }
+        let i = y as u64 * self.dimensions.0 as u64 + x as u64;
+        // the first range that ends after the pixel is the only one that may hold it
+        let next = self.ranges.partition_point(|&(_, end)| end <= i);
+        self.ranges
+            .get(next)
+            .map_or(false, |&(start, _)| start <= i)
+    }
+
+    /// number of ignored pixels
+    pub fn ignored_count(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .sum()
+    }
+
+    /// serializes the mask as run lengths of alternating kept and ignored pixels,
+    /// starting with kept ones, after a header of the magic bytes, width and height.
+    /// each run length is an unsigned LEB128.
+    pub fn to_rle(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&self.dimensions.0.to_le_bytes());
+        out.extend_from_slice(&self.dimensions.1.to_le_bytes());
+        let mut kept_from = 0;
+        for &(start, end) in &self.ranges {
+            write_leb128(&mut out, start - kept_from);
+            write_leb128(&mut out, end - start);
+            kept_from = end;
+        }
+        let len = self.dimensions.0 as u64 * self.dimensions.1 as u64;
+        if kept_from < len || self.ranges.is_empty() {
+            write_leb128(&mut out, len - kept_from);
+        }
+        out
+    }
+
+    /// loads a mask serialized by `to_rle`.
+    pub fn from_rle(data: &[u8]) -> Result<Self, PixelmatchError> {
+        if data.len() < 12 || &data[..4] != MAGIC {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+        let width = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let height = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+        let len = width as u64 * height as u64;
+
+        // runs alternate between kept and ignored pixels and must add up to the size in the header
+        let mut ranges: Vec<(u64, u64)> = vec![];
+        let mut total = 0u64;
+        let mut rest = &data[12..];
+        let mut ignored = false;
+        while !rest.is_empty() {
+            let (run, next) = read_leb128(rest).ok_or(PixelmatchError::InvalidFormatError)?;
+            let end = total
+                .checked_add(run)
+                .filter(|&end| end <= len)
+                .ok_or(PixelmatchError::InvalidFormatError)?;
+            if ignored && run > 0 {
+                match ranges.last_mut() {
+                    Some(last) if last.1 == total => last.1 = end,
+                    _ => ranges.push((total, end)),
+                }
+            }
+            (total, ignored, rest) = (end, !ignored, next);
+        }
+        if total != len {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+        Ok(Self {
+            dimensions: (width, height),
+            ranges,
+        })
+    }
+}
+
+/// grows the ignored pixels by `radius` pixels in every direction, including diagonals
+fn dilate(ignored: &mut [bool], dimensions: (u32, u32), radius: usize) {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    if radius == 0 || width == 0 {
+        return;
+    }
+    // a square is the same as a horizontal pass followed by a vertical one
+    let mut rows = vec![false; ignored.len()];
+    for (row, out) in ignored.chunks(width).zip(rows.chunks_mut(width)) {
+        for x in (0..width).filter(|&x| row[x]) {
+            let end = (x + radius + 1).min(width);
+            out[x.saturating_sub(radius)..end].fill(true);
+        }
+    }
+    ignored.fill(false);
+    for y in 0..height {
+        for x in (0..width).filter(|&x| rows[y * width + x]) {
+            for y2 in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                ignored[y2 * width + x] = true;
+            }
+        }
+    }
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_leb128(data: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        // the tenth byte only has room for the highest bit of a u64
+        if i == 9 && byte > 1 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &data[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pixelmatch;
+
+    fn page(caret: bool) -> Vec<u8> {
+        let (w, h) = (12usize, 8usize);
+        let mut img: Vec<u8> = (0..w * h)
+            .flat_map(|i| [(i * 5) as u8, 100, 200, 255])
+            .collect();
+        if caret {
+            for y in 2..5 {
+                img[(y * w + 6) * 4..(y * w + 7) * 4].copy_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+        img
+    }
+
+    fn rle(dimensions: (u32, u32), runs: &[u64]) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&dimensions.0.to_le_bytes());
+        out.extend_from_slice(&dimensions.1.to_le_bytes());
+        for &run in runs {
+            write_leb128(&mut out, run);
+        }
+        out
+    }
+
+    #[test]
+    fn should_learn_varying_pixels() {
+        let (on, off) = (page(true), page(false));
+        let mask = IgnoreMask::learn(&[&on, &off, &on], (12, 8), None, None).unwrap();
+        // the caret column, grown by two pixels
+        assert_eq!(mask.ignored_count(), 5 * 7);
+        assert!(mask.is_ignored(4, 0) && mask.is_ignored(8, 6));
+        assert!(!mask.is_ignored(3, 0) && !mask.is_ignored(6, 7));
+
+        let mask = IgnoreMask::learn(
+            &[&on, &off],
+            (12, 8),
+            Some(MaskOption { dilation: 0 }),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mask.ignored_count(), 3);
+        assert!(IgnoreMask::learn(&[&on, &off[4..]], (12, 8), None, None).is_err());
+    }
+
+    #[test]
+    fn should_ignore_blinking_caret_in_later_comparisons() {
+        let (on, off) = (page(true), page(false));
+        let mask = IgnoreMask::learn(&[&on, &off, &on], (12, 8), None, None).unwrap();
+        let mut changed = off;
+        changed[..4].copy_from_slice(&[255, 0, 0, 255]);
+        let options = PixelmatchOption {
+            ignore_mask: Some(mask),
+            ..PixelmatchOption::default()
+        };
+        let result = pixelmatch(&on, &changed, (12, 8), Some(options)).unwrap();
+        assert_eq!(result.diff_count, 1);
+    }
+
+    #[test]
+    fn should_round_trip_masks_as_run_lengths() {
+        let (on, off) = (page(true), page(false));
+        let mask = IgnoreMask::learn(&[&on, &off], (12, 8), None, None).unwrap();
+        assert_eq!(IgnoreMask::from_rle(&mask.to_rle()).unwrap(), mask);
+        let empty = IgnoreMask::learn(&[&on, &on], (12, 8), None, None).unwrap();
+        assert_eq!(empty.to_rle(), rle((12, 8), &[96]));
+        assert_eq!(IgnoreMask::from_rle(&empty.to_rle()).unwrap(), empty);
+
+        // the largest mask is a handful of bytes in memory as well
+        let largest = IgnoreMask::from_rle(&rle((0xffff, 0xffff), &[0xffff * 0xffff])).unwrap();
+        assert_eq!(largest.ignored_count(), 0);
+        assert!(!largest.is_ignored(0xfffe, 0xfffe));
+        let largest = IgnoreMask::from_rle(&rle((0xffff, 0xffff), &[0, 0xffff * 0xffff])).unwrap();
+        assert_eq!(largest.ignored_count(), 0xffff * 0xffff);
+
+        let mut out = vec![];
+        write_leb128(&mut out, u64::MAX);
+        assert_eq!(out.len(), 10);
+        assert_eq!(read_leb128(&out), Some((u64::MAX, &[][..])));
+    }
+
+    #[test]
+    fn should_reject_corrupt_masks() {
+        let valid = rle((12, 8), &[10, 5, 81]);
+        assert!(IgnoreMask::from_rle(&valid).is_ok());
+        let mut bad_magic = valid.clone();
+        bad_magic[0] = b'X';
+        let mut truncated = valid.clone();
+        truncated.push(0x80);
+        // a run whose bits beyond 64 would be dropped, leaving the size in the header
+        let mut overflowing = rle((12, 8), &[]);
+        overflowing.extend_from_slice(&[0x80 | 96, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80]);
+        overflowing.push(2);
+        let mut huge = rle((12, 8), &[]);
+        huge.extend_from_slice(&[0xff; 8]);
+        huge.push(1);
+        for data in [
+            &valid[..11],
+            &bad_magic,
+            &truncated,
+            &overflowing,
+            &huge,
+            &rle((12, 8), &[10, 5]),
+            &rle((12, 8), &[10, 5, 82]),
+            &rle((12, 8), &[u64::MAX, 1]),
+        ] {
+            assert_eq!(
+                IgnoreMask::from_rle(data),
+                Err(PixelmatchError::InvalidFormatError)
+            );
+        }
+    }
+}
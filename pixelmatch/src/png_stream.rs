@@ -0,0 +1,272 @@
+use super::stream::StreamComparison;
+use super::{PixelmatchError, PixelmatchOption};
+use png::{BitDepth, ColorType, Decoder, Encoder, Reader, Transformations};
+use std::io::{Read, Write};
+
+/// an error of compare_png
+#[derive(Debug)]
+pub enum PngStreamError {
+    /// one of the images could not be decoded
+    Decoding(png::DecodingError),
+    /// the diff image could not be encoded or written
+    Encoding(png::EncodingError),
+    /// the images are not the same size
+    DimensionMismatch,
+    /// one of the images is interlaced, so it can't be read row by row
+    Interlaced,
+    /// one of the images ended before all of its rows were decoded
+    MissingRows,
+    /// the comparison itself failed
+    Pixelmatch(PixelmatchError),
+}
+
+impl std::fmt::Display for PngStreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PngStreamError::Decoding(err) => write!(f, "png decoding error: {err}"),
+            PngStreamError::Encoding(err) => write!(f, "png encoding error: {err}"),
+            PngStreamError::DimensionMismatch => f.write_str("images are not the same size"),
+            PngStreamError::Interlaced => f.write_str("interlaced png is not supported"),
+            PngStreamError::MissingRows => f.write_str("png image ended before its last row"),
+            PngStreamError::Pixelmatch(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PngStreamError {}
+
+impl From<png::DecodingError> for PngStreamError {
+    fn from(err: png::DecodingError) -> Self {
+        PngStreamError::Decoding(err)
+    }
+}
+
+impl From<png::EncodingError> for PngStreamError {
+    fn from(err: png::EncodingError) -> Self {
+        PngStreamError::Encoding(err)
+    }
+}
+
+impl From<PixelmatchError> for PngStreamError {
+    fn from(err: PixelmatchError) -> Self {
+        PngStreamError::Pixelmatch(err)
+    }
+}
+
+/// an output of compare_png
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngStreamOutput {
+    /// difference count
+    pub diff_count: u64,
+    /// count of differing pixels found nearby in the other image; see `shift_radius`
+    pub shifted_count: u64,
+}
+
+/// compares two PNG images by decoding them scanline by scanline in lockstep,
+/// and writes the diff image as a PNG while it is produced.
+///
+/// besides the decoder and encoder state, only a window of rows of each image is held
+/// in memory: the row being compared and two rows on each side, or `shift_radius` rows
+/// if more. a pixel's neighbors alone would fit in three rows, but the anti-aliasing detection
+/// also checks whether the darkest and brightest neighbors have siblings of the same color,
+/// which reaches two rows away; with fewer rows the diff image would differ from `pixelmatch`.
+///
+/// # Arguments
+///
+/// * `img1` - a reader of the base PNG image.
+/// * `img2` - a reader of the target PNG image.
+/// * `diff_image` - a writer for the diff PNG image, if any.
+/// * `options` - an option for pixelmatch.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pixelmatch::png_stream::*;
+/// use std::fs::File;
+/// use std::io::{BufReader, BufWriter};
+///
+/// let img1 = BufReader::new(File::open("a.png").unwrap());
+/// let img2 = BufReader::new(File::open("b.png").unwrap());
+/// let diff = BufWriter::new(File::create("diff.png").unwrap());
+/// let output = compare_png(img1, img2, Some(diff), None).unwrap();
+/// println!("{} pixels differ", output.diff_count);
+/// ```
+pub fn compare_png<R1: Read, R2: Read, W: Write>(
+    img1: R1,
+    img2: R2,
+    diff_image: Option<W>,
+    options: Option<PixelmatchOption>,
+) -> Result<PngStreamOutput, PngStreamError> {
+    let mut reader1 = read_header(img1)?;
+    let mut reader2 = read_header(img2)?;
+    let dimensions = (reader1.info().width, reader1.info().height);
+    if dimensions != (reader2.info().width, reader2.info().height) {
+        return Err(PngStreamError::DimensionMismatch);
+    }
+
+    let mut writer = match diff_image {
+        Some(w) => {
+            let mut encoder = Encoder::new(w, dimensions.0, dimensions.1);
+            encoder.set_color(ColorType::Rgba);
+            encoder.set_depth(BitDepth::Eight);
+            Some(encoder.write_header()?)
+        }
+        None => None,
+    };
+    let color1 = reader1.output_color_type().0;
+    let color2 = reader2.output_color_type().0;
+    let mut stream = StreamComparison::new(dimensions, options);
+    {
+        let mut stream_writer = match writer.as_mut() {
+            Some(w) => Some(w.stream_writer()?),
+            None => None,
+        };
+        let (mut row1, mut row2) = (vec![], vec![]);
+        for _ in 0..dimensions.1 {
+            let (Some(r1), Some(r2)) = (reader1.next_row()?, reader2.next_row()?) else {
+                return Err(PngStreamError::MissingRows);
+            };
+            to_rgba(r1.data(), color1, &mut row1);
+            to_rgba(r2.data(), color2, &mut row2);
+            let diff_rows = stream.push(&row1, &row2)?;
+            if let Some(w) = stream_writer.as_mut() {
+                w.write_all(&diff_rows).map_err(png::EncodingError::from)?;
+            }
+        }
+        if let Some(w) = stream_writer {
+            w.finish()?;
+        }
+    }
+    if let Some(w) = writer {
+        w.finish()?;
+    }
+    // the decoders yield exactly the rows of their headers, which are the same height
+    assert!(reader1.next_row()?.is_none() && reader2.next_row()?.is_none());
+    assert!(stream.is_done());
+    Ok(PngStreamOutput {
+        diff_count: stream.diff_count(),
+        shifted_count: stream.shifted_count(),
+    })
+}
+
+/// decodes a whole PNG image into rgba 24bit image data; returns it with the size of the image.
+///
+/// gray, gray alpha, rgb and palette images of any bit depth are expanded to 8 bit rgba.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pixelmatch::png_stream::*;
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// let (image, dimensions) = decode_png(BufReader::new(File::open("a.png").unwrap())).unwrap();
+/// ```
+pub fn decode_png<R: Read>(r: R) -> Result<(Vec<u8>, (u32, u32)), PngStreamError> {
+    let mut decoder = Decoder::new(r);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf)?;
+    let mut rgba = vec![];
+    to_rgba(&buf[..info.buffer_size()], info.color_type, &mut rgba);
+    Ok((rgba, (info.width, info.height)))
+}
+
+/// encodes rgba 24bit image data, such as a diff image, as a PNG image.
+pub fn encode_png<W: Write>(
+    w: W,
+    image: &[u8],
+    dimensions: (u32, u32),
+) -> Result<(), PngStreamError> {
+    let mut encoder = Encoder::new(w, dimensions.0, dimensions.1);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.write_header()?.write_image_data(image)?;
+    Ok(())
+}
+
+fn read_header<R: Read>(r: R) -> Result<Reader<R>, PngStreamError> {
+    let mut decoder = Decoder::new(r);
+    // 8 bit gray, gray alpha, rgb or rgba; palettes and tRNS chunks are expanded
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let reader = decoder.read_info()?;
+    if reader.info().interlaced {
+        return Err(PngStreamError::Interlaced);
+    }
+    Ok(reader)
+}
+
+fn to_rgba(data: &[u8], color: ColorType, out: &mut Vec<u8>) {
+    out.clear();
+    match color {
+        ColorType::Grayscale => out.extend(data.iter().flat_map(|&v| [v, v, v, 255])),
+        ColorType::GrayscaleAlpha => {
+            out.extend(data.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]))
+        }
+        ColorType::Rgb => out.extend(data.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255])),
+        // indexed images have been expanded by the decoder
+        ColorType::Rgba | ColorType::Indexed => out.extend_from_slice(data),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pixelmatch;
+
+    fn encode(img: &[u8], dimensions: (u32, u32)) -> Vec<u8> {
+        let mut out = vec![];
+        encode_png(&mut out, img, dimensions).unwrap();
+        out
+    }
+
+    #[test]
+    fn should_stream_the_same_diff_image() {
+        let (w, h) = (6u32, 5u32);
+        let img1: Vec<u8> = (0..w * h * 4).map(|i| (i * 7 % 256) as u8).collect();
+        let mut img2 = img1.clone();
+        img2[40..48].copy_from_slice(&[0, 0, 0, 255, 255, 255, 255, 255]);
+        let png1 = encode(&img1, (w, h));
+        let png2 = encode(&img2, (w, h));
+
+        let mut diff_png = vec![];
+        let output = compare_png(&png1[..], &png2[..], Some(&mut diff_png), None).unwrap();
+
+        let expected = pixelmatch(&img1, &img2, (w, h), None).unwrap();
+        assert_eq!(output.diff_count as usize, expected.diff_count);
+        assert_eq!(
+            decode_png(&diff_png[..]).unwrap(),
+            (expected.diff_image, (w, h))
+        );
+    }
+
+    #[test]
+    fn should_count_shifted_pixels() {
+        let (w, h) = (8u32, 6u32);
+        let img1: Vec<u8> = (0..w * h)
+            .flat_map(|i| if i % w == 3 { [0, 0, 0, 255] } else { [255; 4] })
+            .collect();
+        // the dark column moved right by a pixel
+        let img2: Vec<u8> = (0..w * h)
+            .flat_map(|i| if i % w == 4 { [0, 0, 0, 255] } else { [255; 4] })
+            .collect();
+        let options = PixelmatchOption {
+            shift_radius: 1,
+            ..PixelmatchOption::default()
+        };
+        let (png1, png2) = (encode(&img1, (w, h)), encode(&img2, (w, h)));
+
+        let output =
+            compare_png(&png1[..], &png2[..], None::<Vec<u8>>, Some(options.clone())).unwrap();
+        let expected = pixelmatch(&img1, &img2, (w, h), Some(options)).unwrap();
+        assert!(expected.shifted_count > 0);
+        assert_eq!(
+            output,
+            PngStreamOutput {
+                diff_count: expected.diff_count as u64,
+                shifted_count: expected.shifted_count as u64,
+            }
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use super::{PixelmatchError, Rgba};
+use core::cmp;
+
+/// a bounding box of a cluster of differing pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// left edge in pixels
+    pub x: u32,
+    /// top edge in pixels
+    pub y: u32,
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+    /// number of differing pixels inside the cluster
+    pub pixel_count: usize,
+}
+
+/// finds clusters of 8-connected pixels drawn with `color` in a diff image.
+///
+/// # Arguments
+///
+/// * `diff_image` - a difference image returned by `pixelmatch`.
+/// * `dimensions` - a size of image.
+/// * `color` - a color of differing pixels; `diff_color` of `PixelmatchOption`.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::*;
+/// use pixelmatch::region::*;
+///
+/// let img1 = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let result = pixelmatch(&img1, &img2, (2, 2), None).unwrap();
+/// let regions = find_regions(&result.diff_image, (2, 2), DEFAULT_DIFF_COLOR).unwrap();
+/// assert_eq!(regions.len(), 1);
+/// ```
+pub fn find_regions(
+    diff_image: &[u8],
+    dimensions: (u32, u32),
+    color: Rgba,
+) -> Result<Vec<Region>, PixelmatchError> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    if diff_image.len() != width * height * 4 {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+    let is_diff = |i: usize| {
+        let pos = i * 4;
+        (
+            diff_image[pos],
+            diff_image[pos + 1],
+            diff_image[pos + 2],
+            diff_image[pos + 3],
+        ) == color
+    };
+
+    let mut visited = vec![false; width * height];
+    let mut regions = vec![];
+    let mut stack = vec![];
+    for start in 0..width * height {
+        if visited[start] || !is_diff(start) {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let (mut x0, mut y0) = (start % width, start / width);
+        let (mut x1, mut y1) = (x0, y0);
+        let mut pixel_count = 0;
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % width, i / width);
+            pixel_count += 1;
+            x0 = cmp::min(x0, x);
+            y0 = cmp::min(y0, y);
+            x1 = cmp::max(x1, x);
+            y1 = cmp::max(y1, y);
+            // go through 8 adjacent pixels
+            for ny in y.saturating_sub(1)..=cmp::min(y + 1, height - 1) {
+                for nx in x.saturating_sub(1)..=cmp::min(x + 1, width - 1) {
+                    let n = ny * width + nx;
+                    if !visited[n] && is_diff(n) {
+                        visited[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        regions.push(Region {
+            x: x0 as u32,
+            y: y0 as u32,
+            width: (x1 - x0 + 1) as u32,
+            height: (y1 - y0 + 1) as u32,
+            pixel_count,
+        });
+    }
+    Ok(regions)
+}
+
+/// merges regions whose bounding boxes are at most `distance` pixels apart,
+/// so that e.g. the glyphs of a changed word end up in a single region.
+pub fn merge_regions(regions: &[Region], distance: u32) -> Vec<Region> {
+    let mut merged: Vec<Region> = regions.to_vec();
+    // a merged box may reach regions that none of its parts did, so repeat until it settles
+    loop {
+        let count = merged.len();
+        merged = merge_near(&merged, distance);
+        if merged.len() == count {
+            break;
+        }
+    }
+    merged.sort_by_key(|r| (r.y, r.x));
+    merged
+}
+
+/// merges each group of regions connected by `near` into one region.
+///
+/// regions are bucketed into a grid of cells about the size of a region, so that only
+/// regions sharing a cell within `distance` are compared.
+fn merge_near(regions: &[Region], distance: u32) -> Vec<Region> {
+    if regions.is_empty() {
+        return vec![];
+    }
+    let extent: u64 = regions
+        .iter()
+        .map(|r| cmp::max(r.width, r.height) as u64)
+        .sum();
+    let cell = cmp::max(extent / regions.len() as u64, distance as u64 + 1);
+    // cells covered by a region grown by `grow` pixels on every side
+    let cells = |r: &Region, grow: u64| {
+        let span = |start: u32, len: u32| {
+            (start as u64).saturating_sub(grow) / cell..=(start as u64 + len as u64 + grow) / cell
+        };
+        let rows = span(r.y, r.height);
+        span(r.x, r.width).flat_map(move |cx| rows.clone().map(move |cy| (cx, cy)))
+    };
+
+    // (cell, region) pairs sorted by cell, so that the regions of a cell are a run
+    let mut grid: Vec<((u64, u64), usize)> = regions
+        .iter()
+        .enumerate()
+        .flat_map(|(i, region)| cells(region, 0).map(move |key| (key, i)))
+        .collect();
+    grid.sort_unstable();
+    let mut parent: Vec<usize> = (0..regions.len()).collect();
+    for (i, region) in regions.iter().enumerate() {
+        for key in cells(region, distance as u64) {
+            let start = grid.partition_point(|&(other, _)| other < key);
+            let run = grid[start..].iter().take_while(|&&(other, _)| other == key);
+            for &(_, j) in run {
+                if j > i && near(region, &regions[j], distance) {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[cmp::max(a, b)] = cmp::min(a, b);
+                }
+            }
+        }
+    }
+
+    let mut merged: Vec<Region> = vec![];
+    let mut index = vec![None; regions.len()];
+    for (i, region) in regions.iter().enumerate() {
+        let root = find(&mut parent, i);
+        match index[root] {
+            Some(k) => merged[k] = union(&merged[k], region),
+            None => {
+                index[root] = Some(merged.len());
+                merged.push(*region);
+            }
+        }
+    }
+    merged
+}
+
+/// the representative of the group of `i`, halving the path on the way
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn near(a: &Region, b: &Region, distance: u32) -> bool {
+    let gap = |a0: u32, a1: u32, b0: u32, b1: u32| {
+        if a1 <= b0 {
+            b0 - a1
+        } else if b1 <= a0 {
+            a0 - b1
+        } else {
+            0
+        }
+    };
+    gap(a.x, a.x + a.width, b.x, b.x + b.width) <= distance
+        && gap(a.y, a.y + a.height, b.y, b.y + b.height) <= distance
+}
+
+fn union(a: &Region, b: &Region) -> Region {
+    let (x, y) = (cmp::min(a.x, b.x), cmp::min(a.y, b.y));
+    Region {
+        x,
+        y,
+        width: cmp::max(a.x + a.width, b.x + b.width) - x,
+        height: cmp::max(a.y + a.height, b.y + b.height) - y,
+        pixel_count: a.pixel_count + b.pixel_count,
+    }
+}
+
+/// draws a rectangle of `thickness` pixels just outside of a region, clipped to the image.
+pub(crate) fn draw_outline(
+    img: &mut [u8],
+    dimensions: (u32, u32),
+    region: &Region,
+    color: Rgba,
+    thickness: u32,
+) {
+    let (width, height) = (dimensions.0 as i64, dimensions.1 as i64);
+    let t = thickness as i64;
+    let (left, top) = (region.x as i64 - t, region.y as i64 - t);
+    let (right, bottom) = (
+        region.x as i64 + region.width as i64 + t,
+        region.y as i64 + region.height as i64 + t,
+    );
+    for y in cmp::max(top, 0)..cmp::min(bottom, height) {
+        for x in cmp::max(left, 0)..cmp::min(right, width) {
+            let inside = x >= left + t && x < right - t && y >= top + t && y < bottom - t;
+            if !inside {
+                let pos = ((y * width + x) * 4) as usize;
+                img[pos] = color.0;
+                img[pos + 1] = color.1;
+                img[pos + 2] = color.2;
+                img[pos + 3] = color.3;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_find_separate_clusters() {
+        let (w, h) = (6, 3);
+        let mut diff = vec![0; w * h * 4];
+        for &(x, y) in &[(0, 0), (1, 1), (4, 0), (5, 0), (5, 2)] {
+            diff[(y * w + x) * 4..(y * w + x) * 4 + 4].copy_from_slice(&[255, 0, 0, 255]);
+        }
+        let regions = find_regions(&diff, (w as u32, h as u32), (255, 0, 0, 255)).unwrap();
+        assert_eq!(
+            regions,
+            vec![
+                Region {
+                    x: 0,
+                    y: 0,
+                    width: 2,
+                    height: 2,
+                    pixel_count: 2
+                },
+                Region {
+                    x: 4,
+                    y: 0,
+                    width: 2,
+                    height: 1,
+                    pixel_count: 2
+                },
+                Region {
+                    x: 5,
+                    y: 2,
+                    width: 1,
+                    height: 1,
+                    pixel_count: 1
+                },
+            ]
+        );
+        let merged = merge_regions(&regions, 1);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[1].pixel_count, 3);
+    }
+
+    #[test]
+    fn should_merge_like_pairwise_merging() {
+        let region = |x, y, width, height| Region {
+            x,
+            y,
+            width,
+            height,
+            pixel_count: 1,
+        };
+        // merging the two small regions makes a box that reaches the third one
+        let grown = [region(0, 0, 2, 2), region(3, 0, 2, 10), region(0, 11, 1, 1)];
+        assert_eq!(merge_regions(&grown, 0).len(), 3);
+        assert_eq!(merge_regions(&grown, 1).len(), 1);
+
+        // regions scattered by a linear congruential generator
+        let mut seed = 7u32;
+        let mut next = |modulo: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % modulo
+        };
+        let regions: Vec<Region> = (0..300)
+            .map(|_| region(next(400), next(400), next(12) + 1, next(6) + 1))
+            .collect();
+        for distance in [0, 3, 10] {
+            let mut expected = regions.clone();
+            'outer: loop {
+                for i in 0..expected.len() {
+                    for j in i + 1..expected.len() {
+                        if near(&expected[i], &expected[j], distance) {
+                            let other = expected.swap_remove(j);
+                            expected[i] = union(&expected[i], &other);
+                            continue 'outer;
+                        }
+                    }
+                }
+                break;
+            }
+            expected.sort_by_key(|r| (r.y, r.x, r.width, r.height));
+            let mut merged = merge_regions(&regions, distance);
+            merged.sort_by_key(|r| (r.y, r.x, r.width, r.height));
+            assert_eq!(merged, expected);
+        }
+    }
+
+    #[test]
+    fn should_reject_a_short_diff_image() {
+        assert_eq!(
+            find_regions(&[0; 12], (2, 2), (255, 0, 0, 255)),
+            Err(PixelmatchError::ImageLengthError)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use super::{compare_row, Compositor, PixelmatchError, PixelmatchOption};
+use core::cmp;
+
+/// rows needed on each side of a row to detect anti-aliasing:
+/// the adjacent pixels of a pixel and their own adjacent pixels.
+/// the shift search may need more.
+const HALO: usize = 2;
+
+/// compares two images that are fed a strip of rows at a time and emits the diff image
+/// row by row, so that neither the images nor the diff image have to be held in memory at once.
+///
+/// only the rows still needed for the anti-aliasing detection are kept, which makes it possible
+/// to compare images larger than the address space allows for a whole rgba buffer.
+/// the result is identical to `pixelmatch`, except that `hash_check` and `alignment`
+/// are ignored since they need the whole images.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::stream::*;
+///
+/// let img1 = vec![255, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let img2 = vec![0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// let mut stream = StreamComparison::new((2, 2), None);
+/// let mut diff_image = stream.push(&img1[..8], &img2[..8]).unwrap();
+/// diff_image.extend(stream.push(&img1[8..], &img2[8..]).unwrap());
+/// assert!(stream.is_done());
+/// assert_eq!(stream.diff_count(), 1);
+/// assert_eq!(diff_image.len(), 16);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StreamComparison {
+    dimensions: (u32, u32),
+    options: PixelmatchOption,
+    max_delta: f32,
+    compositor1: Compositor,
+    compositor2: Compositor,
+    /// buffered rows of img1, starting at `buffered_from`
+    rows1: Vec<u8>,
+    /// buffered rows of img2, starting at `buffered_from`
+    rows2: Vec<u8>,
+    buffered_from: usize,
+    rows_in: usize,
+    rows_out: usize,
+    /// rows needed on each side of a row
+    halo: usize,
+    diff_count: u64,
+    shifted_count: u64,
+}
+
+impl StreamComparison {
+    /// starts a comparison of two images of `dimensions`.
+    pub fn new(dimensions: (u32, u32), options: Option<PixelmatchOption>) -> Self {
+        let options = options.unwrap_or_default();
+        Self {
+            dimensions,
+            max_delta: options.comparison_mode.max_delta(options.threshold),
+            compositor1: Compositor::new(&options, options.img1_alpha_mode, dimensions.0),
+            compositor2: Compositor::new(&options, options.img2_alpha_mode, dimensions.0),
+            halo: cmp::max(HALO, options.shift_radius as usize),
+            options,
+            rows1: vec![],
+            rows2: vec![],
+            buffered_from: 0,
+            rows_in: 0,
+            rows_out: 0,
+            diff_count: 0,
+            shifted_count: 0,
+        }
+    }
+
+    /// feeds the next rows of img1 and img2, and returns the rows of the diff image
+    /// that could be completed with them; possibly none.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows1` - whole rgba 24bit rows of img1.
+    /// * `rows2` - the same rows of img2.
+    pub fn push(&mut self, rows1: &[u8], rows2: &[u8]) -> Result<Vec<u8>, PixelmatchError> {
+        if rows1.len() != rows2.len() {
+            return Err(PixelmatchError::ImageLengthError);
+        }
+        if let Some(mask) = &self.options.ignore_mask {
+            if mask.dimensions() != self.dimensions {
+                return Err(PixelmatchError::InvalidFormatError);
+            }
+        }
+        let (width, height) = (self.dimensions.0 as usize, self.dimensions.1 as usize);
+        let stride = width * 4;
+        if stride == 0 {
+            return Ok(vec![]);
+        }
+        if rows1.len() % stride != 0 || self.rows_in + rows1.len() / stride > height {
+            return Err(PixelmatchError::InvalidFormatError);
+        }
+        self.rows1.extend_from_slice(rows1);
+        self.rows2.extend_from_slice(rows2);
+        self.rows_in += rows1.len() / stride;
+
+        let mut diff_rows = vec![];
+        let mut first_row = self.buffered_from;
+        while self.rows_out < self.rows_in
+            && (self.rows_in == height || self.rows_in - self.rows_out > self.halo)
+        {
+            let y = self.rows_out;
+            // the window ends where the image does, so the edges are handled the same way
+            let last_row = cmp::min(y + self.halo, height - 1);
+            let offset = (first_row - self.buffered_from) * stride;
+            let end = (last_row + 1 - self.buffered_from) * stride;
+            let window = (width as u32, (last_row + 1 - first_row) as u32);
+            self.compositor1.first_row = first_row;
+            self.compositor2.first_row = first_row;
+
+            let start = diff_rows.len();
+            diff_rows.resize(start + stride, 0);
+            let (diff, shifted) = compare_row(
+                (&self.rows1[offset..end], &self.compositor1),
+                (&self.rows2[offset..end], &self.compositor2),
+                y - first_row,
+                window,
+                &self.options,
+                self.max_delta,
+                (&mut diff_rows[start..], None),
+            );
+            self.diff_count += diff as u64;
+            self.shifted_count += shifted as u64;
+
+            self.rows_out += 1;
+            first_row = self.rows_out.saturating_sub(self.halo);
+        }
+
+        // forget the rows that no later row looks at
+        let consumed = (first_row - self.buffered_from) * stride;
+        self.rows1.drain(..consumed);
+        self.rows2.drain(..consumed);
+        self.buffered_from = first_row;
+
+        Ok(diff_rows)
+    }
+
+    /// difference count of the rows emitted so far
+    pub fn diff_count(&self) -> u64 {
+        self.diff_count
+    }
+
+    /// shifted count of the rows emitted so far
+    pub fn shifted_count(&self) -> u64 {
+        self.shifted_count
+    }
+
+    /// whether every row of the diff image has been emitted
+    pub fn is_done(&self) -> bool {
+        self.rows_out == self.dimensions.1 as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mask::IgnoreMask;
+    use crate::{pixelmatch, Backdrop};
+
+    #[test]
+    fn should_match_pixelmatch_for_any_strip_height() {
+        let (w, h) = (7usize, 9usize);
+        // a noisy pattern with some edges for the anti-aliasing detection to look at
+        let pattern = |seed: usize| {
+            (0..w * h)
+                .flat_map(|i| {
+                    let v = ((i * seed) % 13 * 20) as u8;
+                    [v, v / 2, 255 - v, if i % 5 == 0 { 128 } else { 255 }]
+                })
+                .collect::<Vec<u8>>()
+        };
+        let (img1, img2) = (pattern(7), pattern(11));
+        for shift_radius in [0, 3] {
+            let options = PixelmatchOption {
+                include_anti_alias: true,
+                backdrop: Backdrop::Checkerboard {
+                    size: 2,
+                    light: (255, 255, 255),
+                    dark: (200, 200, 200),
+                },
+                shift_radius,
+                ..PixelmatchOption::default()
+            };
+            let expected =
+                pixelmatch(&img1, &img2, (w as u32, h as u32), Some(options.clone())).unwrap();
+
+            for rows in 1..=h {
+                let mut stream = StreamComparison::new((w as u32, h as u32), Some(options.clone()));
+                let mut diff_image = vec![];
+                for (strip1, strip2) in img1.chunks(rows * w * 4).zip(img2.chunks(rows * w * 4)) {
+                    diff_image.extend(stream.push(strip1, strip2).unwrap());
+                }
+                assert!(stream.is_done());
+                assert_eq!(stream.diff_count() as usize, expected.diff_count);
+                assert_eq!(stream.shifted_count(), expected.shifted_count as u64);
+                assert_eq!(diff_image, expected.diff_image);
+            }
+        }
+    }
+
+    #[test]
+    fn should_reject_an_ignore_mask_of_another_size() {
+        let img = vec![0; 2 * 4];
+        let mask = IgnoreMask::learn(&[&img, &img], (2, 1), None, None).unwrap();
+        let options = PixelmatchOption {
+            ignore_mask: Some(mask),
+            ..PixelmatchOption::default()
+        };
+        let mut stream = StreamComparison::new((1, 2), Some(options));
+        assert_eq!(
+            stream.push(&img, &img),
+            Err(PixelmatchError::InvalidFormatError)
+        );
+    }
+}
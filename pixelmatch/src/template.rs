@@ -0,0 +1,309 @@
+use super::{pixelmatch, squared_delta, Backdrop, Compositor, PixelmatchError, PixelmatchOption};
+use core::cmp;
+
+/// largest possible YIQ distance, used to scale scores to 0-1
+const MAX_YIQ_DELTA: f32 = 35215.0;
+/// the template is not reduced below this size
+const MIN_TEMPLATE_SIZE: usize = 8;
+/// the pyramid is not deeper than this
+const MAX_LEVELS: usize = 4;
+/// best positions at the coarsest level that are refined further
+const CANDIDATES: usize = 8;
+
+/// an output of find_template
+#[derive(Debug)]
+pub struct TemplateMatch {
+    /// left edge of the best matching window in the image
+    pub x: u32,
+    /// top edge of the best matching window in the image
+    pub y: u32,
+    /// mean YIQ distance between the template and the window, from 0 (identical) to 1
+    pub score: f32,
+    /// difference count of the window, compared with `pixelmatch`
+    pub diff_count: usize,
+    /// a rgba difference image of the window, the size of the template
+    pub diff_image: Vec<u8>,
+}
+
+/// locates the best position of a smaller template image inside a larger image,
+/// and compares the template with that window of the image.
+///
+/// the position is searched coarse to fine over YIQ pyramids of both images, minimizing
+/// the mean of the same YIQ distance that `pixelmatch` uses for each pixel.
+///
+/// # Arguments
+///
+/// * `img` - a rgba 24bit image data slice to search in.
+/// * `dimensions` - a size of img.
+/// * `template` - a rgba 24bit image data slice to look for.
+/// * `template_dimensions` - a size of template; at most the size of img.
+/// * `options` - an option for pixelmatch.
+///
+/// # Examples
+///
+/// ```
+/// use pixelmatch::template::*;
+///
+/// let img: Vec<u8> = (0..16 * 16).flat_map(|i| [(i * 37 % 251) as u8, 0, 0, 255]).collect();
+/// // the 4x4 window at (5, 6)
+/// let template: Vec<u8> = (6..10)
+///     .flat_map(|y| img[(y * 16 + 5) * 4..(y * 16 + 9) * 4].to_vec())
+///     .collect();
+/// let found = find_template(&img, (16, 16), &template, (4, 4), None).unwrap();
+/// assert_eq!((found.x, found.y), (5, 6));
+/// assert_eq!(found.diff_count, 0);
+/// ```
+pub fn find_template(
+    img: &[u8],
+    dimensions: (u32, u32),
+    template: &[u8],
+    template_dimensions: (u32, u32),
+    options: Option<PixelmatchOption>,
+) -> Result<TemplateMatch, PixelmatchError> {
+    let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+    let (t_width, t_height) = (
+        template_dimensions.0 as usize,
+        template_dimensions.1 as usize,
+    );
+    if img.len() != width * height * 4 || template.len() != t_width * t_height * 4 {
+        return Err(PixelmatchError::InvalidFormatError);
+    }
+    if t_width > width || t_height > height {
+        return Err(PixelmatchError::ImageLengthError);
+    }
+    let options = options.unwrap_or_default();
+
+    let mut pyramid = vec![Yiq::new(
+        img,
+        width,
+        height,
+        &Compositor::new(&options, options.img2_alpha_mode, dimensions.0),
+    )];
+    let mut t_pyramid = vec![Yiq::new(
+        template,
+        t_width,
+        t_height,
+        &Compositor::new(&options, options.img1_alpha_mode, template_dimensions.0),
+    )];
+    while pyramid.len() < MAX_LEVELS {
+        let t = t_pyramid.last().unwrap();
+        if t.width / 2 < MIN_TEMPLATE_SIZE || t.height / 2 < MIN_TEMPLATE_SIZE {
+            break;
+        }
+        let next = pyramid.last().unwrap().half();
+        let t_next = t.half();
+        pyramid.push(next);
+        t_pyramid.push(t_next);
+    }
+
+    // an exhaustive search at the coarsest level, keeping a few of the best positions
+    let top = pyramid.len() - 1;
+    let (level, t_level) = (&pyramid[top], &t_pyramid[top]);
+    let mut candidates: Vec<(f32, (usize, usize))> = vec![];
+    for y in 0..=level.height - t_level.height {
+        for x in 0..=level.width - t_level.width {
+            let bound = if candidates.len() < CANDIDATES {
+                f32::INFINITY
+            } else {
+                candidates[CANDIDATES - 1].0
+            };
+            let score = level.distance(t_level, (x, y), bound);
+            if score < bound {
+                let at = candidates.partition_point(|c| c.0 <= score);
+                candidates.insert(at, (score, (x, y)));
+                candidates.truncate(CANDIDATES);
+            }
+        }
+    }
+
+    // refine each candidate down to full resolution and keep the best one
+    let mut best = (f32::INFINITY, (0, 0));
+    for (_, mut position) in candidates {
+        let mut score = f32::INFINITY;
+        for level in (0..top).rev() {
+            let (image, t) = (&pyramid[level], &t_pyramid[level]);
+            let center = (position.0 * 2, position.1 * 2);
+            score = f32::INFINITY;
+            for y in center.1.saturating_sub(2)..=cmp::min(center.1 + 2, image.height - t.height) {
+                for x in center.0.saturating_sub(2)..=cmp::min(center.0 + 2, image.width - t.width)
+                {
+                    let s = image.distance(t, (x, y), score);
+                    if s < score {
+                        score = s;
+                        position = (x, y);
+                    }
+                }
+            }
+        }
+        if top == 0 {
+            score = pyramid[0].distance(&t_pyramid[0], position, f32::INFINITY);
+        }
+        if score < best.0 {
+            best = (score, position);
+        }
+    }
+
+    let (score, (x, y)) = best;
+    let mut window = Vec::with_capacity(template.len());
+    for row in y..y + t_height {
+        window.extend_from_slice(&img[(row * width + x) * 4..(row * width + x + t_width) * 4]);
+    }
+    let result = pixelmatch(template, &window, template_dimensions, Some(options))?;
+
+    Ok(TemplateMatch {
+        x: x as u32,
+        y: y as u32,
+        score: score / MAX_YIQ_DELTA,
+        diff_count: result.diff_count,
+        diff_image: result.diff_image,
+    })
+}
+
+/// an image in YIQ, composited as `pixelmatch` composites it
+struct Yiq {
+    /// YIQ over the backdrop, and over black for `Backdrop::WhiteAndBlack`
+    planes: Vec<Vec<(f32, f32, f32)>>,
+    width: usize,
+    height: usize,
+}
+
+impl Yiq {
+    fn new(img: &[u8], width: usize, height: usize, compositor: &Compositor) -> Self {
+        let passes: &[bool] = match compositor.backdrop {
+            Backdrop::WhiteAndBlack => &[false, true],
+            _ => &[false],
+        };
+        let planes = passes
+            .iter()
+            .map(|&black| {
+                (0..width * height)
+                    .map(|i| compositor.yiq(img, i * 4, black))
+                    .collect()
+            })
+            .collect();
+        Self {
+            planes,
+            width,
+            height,
+        }
+    }
+
+    /// halves the size by averaging 2x2 blocks
+    fn half(&self) -> Self {
+        let (width, height) = (cmp::max(self.width / 2, 1), cmp::max(self.height / 2, 1));
+        let planes = self
+            .planes
+            .iter()
+            .map(|plane| {
+                let at = |x: usize, y: usize| {
+                    plane[cmp::min(y, self.height - 1) * self.width + cmp::min(x, self.width - 1)]
+                };
+                let mut data = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let block = [
+                            at(2 * x, 2 * y),
+                            at(2 * x + 1, 2 * y),
+                            at(2 * x, 2 * y + 1),
+                            at(2 * x + 1, 2 * y + 1),
+                        ];
+                        let (y, i, q) = block.iter().fold((0.0, 0.0, 0.0), |sum, p| {
+                            (sum.0 + p.0, sum.1 + p.1, sum.2 + p.2)
+                        });
+                        data.push((y / 4.0, i / 4.0, q / 4.0));
+                    }
+                }
+                data
+            })
+            .collect();
+        Self {
+            planes,
+            width,
+            height,
+        }
+    }
+
+    /// mean YIQ distance between the template and the window at `(x0, y0)`, measured as
+    /// `pixelmatch` measures each pixel; gives up early once the mean can't get below `bound`.
+    fn distance(&self, template: &Yiq, (x0, y0): (usize, usize), bound: f32) -> f32 {
+        let count = (template.width * template.height) as f32;
+        let limit = bound * count;
+        let mut sum = 0.0;
+        for y in 0..template.height {
+            let (start, t_start) = ((y0 + y) * self.width + x0, y * template.width);
+            for x in 0..template.width {
+                sum += self
+                    .planes
+                    .iter()
+                    .zip(&template.planes)
+                    .map(|(plane, t_plane)| {
+                        squared_delta(plane[start + x], t_plane[t_start + x], false)
+                    })
+                    .fold(0.0, f32::max);
+            }
+            if sum >= limit {
+                return f32::INFINITY;
+            }
+        }
+        sum / count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_find_template_through_the_pyramid() {
+        let (w, h) = (120usize, 90usize);
+        let scene = |x: usize, y: usize| {
+            let v = ((x as f32 / 5.0).sin() * (y as f32 / 4.0).cos() * 100.0 + 128.0) as u8;
+            [v, (x * 2) as u8, (y * 3) as u8, 255]
+        };
+        let img: Vec<u8> = (0..w * h).flat_map(|i| scene(i % w, i / w)).collect();
+        let (tx, ty, tw, th) = (71usize, 38usize, 32usize, 24usize);
+        let mut template: Vec<u8> = (0..tw * th)
+            .flat_map(|i| scene(tx + i % tw, ty + i / tw))
+            .collect();
+        // the render differs in one pixel
+        template[..4].copy_from_slice(&[255, 0, 255, 255]);
+
+        let found = find_template(&img, (w as u32, h as u32), &template, (32, 24), None).unwrap();
+        assert_eq!((found.x, found.y), (71, 38));
+        assert_eq!(found.diff_count, 1);
+        assert!(found.score > 0.0 && found.score < 0.01);
+    }
+
+    #[test]
+    fn should_score_over_both_backdrops() {
+        let (w, h) = (32usize, 32usize);
+        let scene =
+            |x: usize, y: usize| [(x * 13 + y * 7) as u8, (x * y) as u8, (y * 29) as u8, 255];
+        let img: Vec<u8> = (0..w * h).flat_map(|i| scene(i % w, i / w)).collect();
+        let (tx, ty, tw, th) = (9usize, 11usize, 16usize, 16usize);
+        let mut template: Vec<u8> = (0..tw * th)
+            .flat_map(|i| {
+                let pos = ((ty + i / tw) * w + tx + i % tw) * 4;
+                img[pos..pos + 4].to_vec()
+            })
+            .collect();
+        // a transparent white pixel only differs from an opaque one over black
+        template[..4].copy_from_slice(&[255, 255, 255, 0]);
+        let pos = (ty * w + tx) * 4;
+        let mut img = img;
+        img[pos..pos + 4].copy_from_slice(&[255, 255, 255, 255]);
+
+        let dimensions = (w as u32, h as u32);
+        let found = find_template(&img, dimensions, &template, (16, 16), None).unwrap();
+        assert_eq!((found.x, found.y, found.diff_count), (9, 11, 0));
+        assert_eq!(found.score, 0.0);
+
+        let options = PixelmatchOption {
+            backdrop: Backdrop::WhiteAndBlack,
+            ..PixelmatchOption::default()
+        };
+        let found = find_template(&img, dimensions, &template, (16, 16), Some(options)).unwrap();
+        assert_eq!((found.x, found.y, found.diff_count), (9, 11, 1));
+        assert!(found.score > 0.0);
+    }
+}
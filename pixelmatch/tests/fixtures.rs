@@ -0,0 +1,101 @@
+use pixelmatch::*;
+
+fn read_fixture(name: &str) -> (Vec<u8>, (u32, u32)) {
+    let img = image::open(format!("../fixtures/{}", name))
+        .unwrap()
+        .to_rgba();
+    let dimensions = img.dimensions();
+    (img.into_raw(), dimensions)
+}
+
+fn diff_count(name: &str, options: PixelmatchOption) -> usize {
+    let (img1, dimensions) = read_fixture(&format!("{}a.png", name));
+    let (img2, _) = read_fixture(&format!("{}b.png", name));
+    pixelmatch(&img1, &img2, dimensions, Some(options))
+        .unwrap()
+        .diff_count
+}
+
+#[test]
+fn should_match_fixtures_in_srgb() {
+    let options = PixelmatchOption {
+        include_anti_alias: true,
+        ..PixelmatchOption::default()
+    };
+    assert_eq!(diff_count("001", options.clone()), 3076);
+    assert_eq!(diff_count("005", options.clone()), 228400);
+    assert_eq!(diff_count("006", options.clone()), 921600);
+    assert_eq!(diff_count("007", options), 462400);
+}
+
+#[test]
+fn should_match_fixtures_in_linear_light() {
+    let options = PixelmatchOption {
+        include_anti_alias: true,
+        linear_light: true,
+        ..PixelmatchOption::default()
+    };
+    assert_eq!(diff_count("001", options.clone()), 3066);
+    let loose = PixelmatchOption {
+        threshold: 0.2,
+        ..options.clone()
+    };
+    assert_eq!(diff_count("001", loose), 2917);
+
+    // a light-tone step that only sRGB sees and a dark-tone step that sRGB misses
+    let img1 = [200, 200, 200, 255, 10, 10, 10, 255];
+    let img2 = [228, 228, 228, 255, 35, 35, 35, 255];
+    let diff_color = options.diff_color;
+    let is_diff = |options: PixelmatchOption| {
+        let output = pixelmatch(&img1, &img2, (2, 1), Some(options)).unwrap();
+        output
+            .diff_image
+            .chunks(4)
+            .map(|p| p == [diff_color.0, diff_color.1, diff_color.2, diff_color.3])
+            .collect::<Vec<bool>>()
+    };
+    assert_eq!(is_diff(PixelmatchOption::default()), [true, false]);
+    assert_eq!(is_diff(options), [false, true]);
+}
+
+#[test]
+fn should_stream_the_same_diff_image() {
+    use pixelmatch::stream::StreamComparison;
+
+    let (img1, dimensions) = read_fixture("001a.png");
+    let (img2, _) = read_fixture("001b.png");
+    let options = PixelmatchOption {
+        include_anti_alias: true,
+        ..PixelmatchOption::default()
+    };
+    let expected = pixelmatch(&img1, &img2, dimensions, Some(options.clone())).unwrap();
+
+    let mut stream = StreamComparison::new(dimensions, Some(options));
+    let strip = dimensions.0 as usize * 4 * 16;
+    let mut diff_image = vec![];
+    for (rows1, rows2) in img1.chunks(strip).zip(img2.chunks(strip)) {
+        diff_image.extend(stream.push(rows1, rows2).unwrap());
+    }
+    assert!(stream.is_done());
+    assert_eq!(stream.diff_count() as usize, expected.diff_count);
+    assert!(diff_image == expected.diff_image);
+}
+
+#[test]
+fn should_compare_with_a_prepared_baseline() {
+    use pixelmatch::comparator::Comparator;
+
+    let options = PixelmatchOption {
+        include_anti_alias: true,
+        ..PixelmatchOption::default()
+    };
+    let (baseline, dimensions) = read_fixture("001a.png");
+    let comparator = Comparator::new(&baseline, dimensions, Some(options.clone())).unwrap();
+    for name in ["001a.png", "001b.png"] {
+        let (img2, _) = read_fixture(name);
+        let expected = pixelmatch(&baseline, &img2, dimensions, Some(options.clone())).unwrap();
+        let result = comparator.compare(&img2).unwrap();
+        assert_eq!(result.diff_count, expected.diff_count);
+        assert!(result.diff_image == expected.diff_image);
+    }
+}